@@ -0,0 +1,43 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use glob::Pattern;
+use revm::primitives::Address;
+use simple_fuzzer::test_support::compile_fixtures;
+use simple_fuzzer::{FuzzConfig, Fuzzer, ParsedFunction, SolidityFuzzer};
+
+/// Generating calldata, which is done for each call.
+fn generate_random_calldata(c: &mut Criterion) {
+    let input = |ty: &str| serde_json::json!({ "type": ty, "internalType": ty });
+    let abi: Vec<ParsedFunction> = serde_json::from_value(serde_json::json!([{
+        "type": "function",
+        "name": "f",
+        "inputs": [
+            input("uint256"),
+            input("address"),
+            input("bytes"),
+            input("string"),
+            input("uint8[]"),
+            input("bytes32[3]"),
+        ],
+        "stateMutability": "nonpayable",
+    }]))
+    .unwrap();
+    let mut fuzzer = SolidityFuzzer::new(abi, 0, vec![Address::ZERO]);
+    c.bench_function("generate_random_calldata", |b| {
+        b.iter(|| fuzzer.generate_random_calldata().1)
+    });
+}
+
+/// A short campaign of always reverting calls against the hand-assembled
+/// contracts of `tests/fixtures`, so that no crash ends it early.
+fn campaign(c: &mut Criterion) {
+    let config = FuzzConfig {
+        include: vec![Pattern::new("fail").unwrap()],
+        max_iterations: Some(1000),
+        ..FuzzConfig::default()
+    };
+    let mut fuzzer = Fuzzer::new(compile_fixtures(&["Reverter"], "ReverterTest"), config).unwrap();
+    c.bench_function("campaign_1000_iterations", |b| b.iter(|| fuzzer.run()));
+}
+
+criterion_group!(benches, generate_random_calldata, campaign);
+criterion_main!(benches);
@@ -0,0 +1,1275 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use glob::Pattern;
+use itertools::Itertools;
+use log::{info, log_enabled, warn, Level};
+use revm::primitives::{Address, ExecutionResult, Halt, SpecId, U256};
+
+use crate::corpus::Corpus;
+use crate::coverage::{Coverage, CoverageInspector};
+use crate::evm::{Evm, RevertReason, SnapshotId, TxError, DEFAULT_CALLER};
+use crate::fuzzer::{function_selector, Call, FunctionSpec, MultiTargetFuzzer, SolidityFuzzer};
+use crate::solc::{constructor, CompilationOutput, ParsedFunction};
+
+/// Initial balance of each sender, large enough to never run out of ether
+/// while sending random amounts to payable functions.
+pub const CALLER_BALANCE: U256 = U256::from_limbs([u64::MAX, u64::MAX, 0, 0]);
+
+pub fn deploy_invariant_checker(
+    runner: &mut Evm,
+    bytecode: Vec<u8>,
+    encoded_args: Vec<u8>,
+) -> Result<Address, SetupError> {
+    runner
+        .try_create_with_args(bytecode, encoded_args)
+        .map_err(SetupError::DeployFailed)
+}
+
+/// Deploy the targets by calling `setup_function()` on the invariant checker,
+/// then return the targets returned by `targets_function()`, either a single
+/// `address` or an `address[]`. Fails if either call fails, or if
+/// `targets_function()` returns anything else or no targets.
+pub fn deploy_targets(
+    runner: &mut Evm,
+    invariant_checker_address: Address,
+    setup_function: &str,
+    targets_function: &str,
+) -> Result<Vec<Address>, SetupError> {
+    let mut call = |function: &str| {
+        let calldata = function_selector(&format!("{function}()"));
+        runner
+            .try_call(invariant_checker_address, calldata.to_vec())
+            .map(|(_, output)| output)
+            .map_err(|err| SetupError::CallFailed(function.to_owned(), err))
+    };
+    call(setup_function)?;
+    let output = call(targets_function)?;
+    match decode_addresses(&output) {
+        Some(targets) if targets.is_empty() => {
+            Err(SetupError::NoTargets(targets_function.to_owned()))
+        }
+        Some(targets) => Ok(targets),
+        None => Err(SetupError::InvalidTargets(
+            targets_function.to_owned(),
+            output,
+        )),
+    }
+}
+
+/// Error setting up a campaign in [`Fuzzer::new`].
+#[derive(Debug)]
+pub enum SetupError {
+    /// The invariant checker has no invariant functions.
+    NoInvariants,
+    /// None of the invariant functions returns a `bool` right after setup.
+    NoValidInvariants,
+    /// The given target contract is given more than once.
+    DuplicateTarget(String),
+    /// Deploying the invariant checker failed.
+    DeployFailed(TxError),
+    /// Calling the given function of the invariant checker failed.
+    CallFailed(String, TxError),
+    /// The given targets function returned the given data, which is neither
+    /// an `address` nor an `address[]`.
+    InvalidTargets(String, Vec<u8>),
+    /// The given targets function returned no targets.
+    NoTargets(String),
+    /// The given targets function returned the given number of targets
+    /// instead of one per target contract.
+    TargetCount(String, usize),
+    /// No target function is left to call once filtered.
+    NoFunctions,
+    /// A weight is given for the named function, which is not a target
+    /// function.
+    UnknownWeight(String),
+    /// The invariant checker's constructor has a parameter of an unsupported
+    /// type, as described.
+    UnsupportedConstructorParam(String),
+    /// The given mutation ratio is not in `0.0..=1.0`.
+    InvalidMutationRatio(f64),
+    /// The number of worker threads is zero.
+    NoJobs,
+}
+
+impl fmt::Display for SetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetupError::NoInvariants => {
+                write!(f, "No invariant_* functions found in invariant checker")
+            }
+            SetupError::NoValidInvariants => write!(
+                f,
+                "No invariant_* functions returning a bool found in invariant checker"
+            ),
+            SetupError::DuplicateTarget(name) => {
+                write!(f, "Target contract '{name}' is given more than once")
+            }
+            SetupError::DeployFailed(err) => write!(f, "Deploying the invariant checker {err}"),
+            SetupError::CallFailed(function, err) => write!(f, "{function}() {err}"),
+            SetupError::InvalidTargets(function, output) => write!(
+                f,
+                "{function}() returned 0x{} instead of an address or address array",
+                hex::encode(output)
+            ),
+            SetupError::NoTargets(function) => write!(f, "{function}() returned no targets"),
+            SetupError::TargetCount(function, count) => write!(
+                f,
+                "{function}() must return one target per target contract, not {count}"
+            ),
+            SetupError::NoFunctions => write!(f, "No target functions to call"),
+            SetupError::UnknownWeight(name) => {
+                write!(f, "Weight given for unknown target function '{name}'")
+            }
+            SetupError::UnsupportedConstructorParam(err) => {
+                write!(f, "{err} in the invariant checker's constructor")
+            }
+            SetupError::InvalidMutationRatio(ratio) => {
+                write!(f, "Mutation ratio must be between 0 and 1, not {ratio}")
+            }
+            SetupError::NoJobs => write!(f, "At least one worker thread is required"),
+        }
+    }
+}
+
+impl std::error::Error for SetupError {}
+
+/// Decode `output` as an ABI-encoded `address` or `address[]`.
+fn decode_addresses(output: &[u8]) -> Option<Vec<Address>> {
+    let address = |word: &[u8]| {
+        word[..12]
+            .iter()
+            .all(|&byte| byte == 0)
+            .then(|| Address::from_slice(&word[12..]))
+    };
+    if output.len() % 32 != 0 {
+        return None;
+    }
+    let words = output.chunks(32).collect_vec();
+    match &words[..] {
+        [word] => Some(vec![address(word)?]),
+        [offset, len, elements @ ..]
+            if U256::from_be_slice(offset) == U256::from(32)
+                && U256::from_be_slice(len) == U256::from(elements.len()) =>
+        {
+            elements.iter().map(|word| address(word)).collect()
+        }
+        _ => None,
+    }
+}
+
+/// An invariant function of the invariant checker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invariant {
+    pub signature: String,
+    /// Selector of `signature`, computed once rather than before each check.
+    pub selector: [u8; 4],
+}
+
+/// Return all invariant functions in `abi`, i.e. functions without
+/// parameters whose name starts with `invariant_`.
+pub fn invariant_functions(abi: &[ParsedFunction]) -> Vec<Invariant> {
+    abi.iter()
+        .filter(|function| {
+            function.ty == "function"
+                && function.name.starts_with("invariant_")
+                && function.inputs.is_empty()
+        })
+        .map(|function| {
+            let signature = format!("{}()", function.name);
+            Invariant {
+                selector: function_selector(&signature),
+                signature,
+            }
+        })
+        .collect()
+}
+
+/// Call `invariant` and return the crash it reveals, if any: the invariant
+/// returning false or anything but an ABI-encoded `bool`, reverting or
+/// halting.
+pub fn check_invariant(
+    runner: &mut Evm,
+    invariant_checker_address: Address,
+    invariant: &Invariant,
+) -> Option<Crash> {
+    let result = match runner.try_call(invariant_checker_address, invariant.selector.to_vec()) {
+        Ok((_, result)) => result,
+        Err(TxError::Failed(ExecutionResult::Revert { output, .. })) => {
+            return Some(Crash::RevertedInvariant(
+                invariant.signature.clone(),
+                output.into(),
+            ))
+        }
+        Err(TxError::Failed(ExecutionResult::Halt { reason, .. })) => {
+            return Some(Crash::HaltedInvariant(invariant.signature.clone(), reason))
+        }
+        Err(TxError::Failed(ExecutionResult::Success { .. })) => unreachable!(),
+        // Invariants are called without value at a zero base fee, so the
+        // call is always valid.
+        Err(TxError::Invalid(err)) => unreachable!("{err}"),
+    };
+    match result.split_last() {
+        Some((&last, init)) if init.len() == 31 && init.iter().all(|&byte| byte == 0) => match last
+        {
+            0 => Some(Crash::BrokenInvariant(invariant.signature.clone())),
+            1 => None,
+            _ => Some(Crash::InvalidInvariantOutput(
+                invariant.signature.clone(),
+                result,
+            )),
+        },
+        _ => Some(Crash::InvalidInvariantOutput(
+            invariant.signature.clone(),
+            result,
+        )),
+    }
+}
+
+/// Apply the sequence of `calls` and return the first crash found: a call
+/// panicking if `panics_are_crashes` is set, or else the first of
+/// `invariants` that is broken, doesn't return a `bool` or reverts
+/// afterwards. The program counters executed by the calls are merged into
+/// `coverage`, if given, and the gas used by each call is pushed to
+/// `gas_used`, `None` if it reverted or halted.
+fn find_crash(
+    runner: &mut Evm,
+    deployment: &Deployment,
+    invariants: &[Invariant],
+    calls: &[Call],
+    panics_are_crashes: bool,
+    mut coverage: Option<&mut Coverage>,
+    mut gas_used: Option<&mut Vec<Option<u64>>>,
+) -> Option<Crash> {
+    let invariant_checker_address = deployment.invariant_checker_address;
+    // A reverting call is expected while fuzzing and leaves the state as is,
+    // so only the invariants, and optionally panics, decide whether this is a
+    // crash.
+    for call in calls {
+        if call.time_delay != 0 {
+            runner.set_block_timestamp(runner.block_timestamp().saturating_add(call.time_delay));
+        }
+        let result = match coverage.as_deref_mut() {
+            Some(coverage) => {
+                let mut inspector = CoverageInspector::default();
+                let result = runner.try_call_as_with_inspector(
+                    call.sender,
+                    call.target,
+                    call.calldata.clone(),
+                    call.value,
+                    &mut inspector,
+                );
+                coverage.merge(inspector, call.target, &call.calldata);
+                result
+            }
+            None => runner.try_call_as(call.sender, call.target, call.calldata.clone(), call.value),
+        };
+        if let Some(gas_used) = gas_used.as_deref_mut() {
+            gas_used.push(result.as_ref().ok().map(|(gas_used, _)| *gas_used));
+        }
+        match result {
+            Ok(_) => {}
+            Err(TxError::Failed(ExecutionResult::Revert { output, .. })) => {
+                if let RevertReason::Panic(code) = RevertReason::decode(&output) {
+                    if panics_are_crashes {
+                        return Some(Crash::Panic(code));
+                    }
+                }
+            }
+            Err(_) => {}
+        }
+    }
+    invariants
+        .iter()
+        .find_map(|invariant| check_invariant(runner, invariant_checker_address, invariant))
+}
+
+/// Gas used by the successful calls to a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasStats {
+    pub calls: u64,
+    pub min: u64,
+    pub max: u64,
+    pub total: u128,
+}
+
+impl Default for GasStats {
+    fn default() -> Self {
+        Self {
+            calls: 0,
+            min: u64::MAX,
+            max: 0,
+            total: 0,
+        }
+    }
+}
+
+impl GasStats {
+    fn record(&mut self, gas_used: u64) {
+        self.calls += 1;
+        self.min = self.min.min(gas_used);
+        self.max = self.max.max(gas_used);
+        self.total += u128::from(gas_used);
+    }
+
+    fn merge(&mut self, other: &GasStats) {
+        self.calls += other.calls;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.total += other.total;
+    }
+
+    /// Mean gas used per call, rounded down.
+    pub fn mean(&self) -> u64 {
+        (self.total / u128::from(self.calls.max(1))) as u64
+    }
+}
+
+/// A crash found by the fuzzer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Crash {
+    /// The given invariant returned false after the calls.
+    BrokenInvariant(String),
+    /// A call reverted with `Panic(uint256)` and the given code.
+    Panic(U256),
+    /// The given invariant returned the given data, which isn't an
+    /// ABI-encoded `bool`, after the calls.
+    InvalidInvariantOutput(String, Vec<u8>),
+    /// The given invariant reverted with the given output after the calls.
+    RevertedInvariant(String, Vec<u8>),
+    /// The given invariant halted for the given reason after the calls, e.g.
+    /// running out of gas.
+    HaltedInvariant(String, Halt),
+}
+
+impl fmt::Display for Crash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Crash::BrokenInvariant(invariant) => write!(f, "Broken invariant: {invariant}"),
+            Crash::Panic(code) => write!(f, "{}", RevertReason::Panic(*code)),
+            Crash::InvalidInvariantOutput(invariant, output) => write!(
+                f,
+                "Invariant {invariant} returned 0x{} instead of a bool",
+                hex::encode(output)
+            ),
+            Crash::RevertedInvariant(invariant, output) => write!(
+                f,
+                "Invariant {invariant} reverted: {}",
+                RevertReason::decode(output)
+            ),
+            Crash::HaltedInvariant(invariant, reason) => {
+                write!(f, "Invariant {invariant} halted: {reason:?}")
+            }
+        }
+    }
+}
+
+/// Settings of a fuzzing campaign run by [`Fuzzer`].
+#[derive(Clone)]
+pub struct FuzzConfig {
+    /// Seed for the random input generator, which also determines the
+    /// constructor arguments of the invariant checker.
+    pub seed: u64,
+    /// Addresses to send calls from, chosen at random for each call.
+    pub senders: Vec<Address>,
+    /// Run each call against the state right after `setUp()` instead of
+    /// accumulating state across up to [`MAX_HISTORY_LEN`] calls.
+    pub isolated: bool,
+    /// Apply sequences of this many calls, each starting from the state right
+    /// after `setUp()`, and check the invariants at the end of each sequence.
+    pub depth: Option<usize>,
+    /// Hardfork to execute with. Defaults to the latest one supported by revm.
+    pub spec_id: Option<SpecId>,
+    /// Upper bound of the random number of seconds `block.timestamp` is
+    /// advanced by before each call.
+    pub max_time_delay: u64,
+    /// Report any call reverting with `Panic(uint256)` as a crash.
+    pub panics: bool,
+    /// Stop after this much time.
+    pub duration: Option<Duration>,
+    /// Stop after this many iterations.
+    pub max_iterations: Option<u64>,
+    /// Draw integer arguments from a dictionary of boundary values some of
+    /// the time.
+    pub dictionary: bool,
+    /// Mutate calls that reach new program counters, found by any worker.
+    pub coverage: bool,
+    /// Directory to load the corpus from and save new entries to.
+    pub corpus_dir: Option<PathBuf>,
+    /// Probability of mutating a corpus entry instead of generating a fresh
+    /// call, if the corpus isn't empty.
+    pub mutation_ratio: f64,
+    /// Number of worker threads fuzzing in parallel.
+    pub jobs: u64,
+    /// Only call target functions whose name matches any of these patterns,
+    /// or all of them if empty.
+    pub include: Vec<Pattern>,
+    /// Don't call target functions whose name matches any of these patterns.
+    pub exclude: Vec<Pattern>,
+    /// Relative probabilities of calling target functions by name or
+    /// signature, 1 for the others.
+    pub weights: Vec<(String, u32)>,
+    /// Name of the function of the invariant checker deploying the targets.
+    pub setup_function: String,
+    /// Name of the function of the invariant checker returning the targets,
+    /// an `address` or an `address[]`. Calls go to a random one of them.
+    pub targets_function: String,
+    /// Record the gas used by the successful calls to each target function.
+    pub gas_report: bool,
+    /// Time between progress reports, logged at the info level.
+    pub stats_interval: Duration,
+}
+
+impl Default for FuzzConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            senders: vec![DEFAULT_CALLER],
+            isolated: false,
+            depth: None,
+            spec_id: None,
+            max_time_delay: 0,
+            panics: false,
+            duration: None,
+            max_iterations: None,
+            dictionary: false,
+            coverage: false,
+            corpus_dir: None,
+            mutation_ratio: 0.5,
+            jobs: 1,
+            include: vec![],
+            exclude: vec![],
+            weights: vec![],
+            setup_function: "setUp".to_owned(),
+            targets_function: "inv".to_owned(),
+            gas_report: false,
+            stats_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Outcome of [`Fuzzer::run`].
+pub struct FuzzResult {
+    /// Number of iterations run by all workers together.
+    pub iterations: u64,
+    /// Number of calls to each target function, keyed by signature, see
+    /// [`MultiTargetFuzzer::function_label`].
+    pub calls_per_function: BTreeMap<String, u64>,
+    /// Gas used by the successful calls to each target function, keyed like
+    /// `calls_per_function`, if [`FuzzConfig::gas_report`] is set.
+    pub gas_report: Option<BTreeMap<String, GasStats>>,
+    /// The crash found, if any.
+    pub crash: Option<FoundCrash>,
+}
+
+/// A crash found by a worker along with its reproducer.
+pub struct FoundCrash {
+    pub crash: Crash,
+    /// Iterations started by all workers when the crash was found.
+    pub iterations: u64,
+    /// Index of the worker that found the crash.
+    pub worker: u64,
+    /// The calls that caused the crash, applied right after `setUp()`.
+    pub calls: Vec<Call>,
+    /// A simpler sequence of calls causing the same crash.
+    pub shrunk_calls: Vec<Call>,
+}
+
+/// An invariant fuzzing campaign against deployed targets.
+pub struct Fuzzer {
+    config: FuzzConfig,
+    /// Name and ABI of each target contract.
+    targets: Vec<(String, Vec<ParsedFunction>)>,
+    /// Invariant functions of the invariant checker.
+    invariants: Vec<Invariant>,
+    deployment: Deployment,
+    /// Generator of the first worker, which also generated the constructor
+    /// arguments of the invariant checker.
+    target_fuzzer: MultiTargetFuzzer,
+    /// ABI-encoded constructor arguments of the invariant checker.
+    constructor_args: Vec<u8>,
+}
+
+impl Fuzzer {
+    /// Deploy the invariant checker of `output` with random constructor
+    /// arguments, if any, and the targets via its setup function, then fund
+    /// the senders of `config`. See [`deploy_targets`].
+    ///
+    /// Invariant functions that revert or don't return a `bool` right after
+    /// setup are skipped with a warning.
+    ///
+    /// With a single target contract, all targets returned by the targets
+    /// function are instances of it. Otherwise the targets function returns
+    /// one target per target contract, in order.
+    ///
+    /// Fails if the invariant checker has no invariant functions left and
+    /// panics aren't reported as crashes either, if a target contract is
+    /// given twice or the number of targets doesn't match, if no target
+    /// function is left to call or a weight names none, if the mutation ratio
+    /// or the number of jobs is out of range, if the constructor has
+    /// unsupported parameters, or if deployment fails.
+    pub fn new(output: CompilationOutput, config: FuzzConfig) -> Result<Self, SetupError> {
+        let invariants = invariant_functions(&output.invariant_checker.1);
+        if invariants.is_empty() && !config.panics {
+            return Err(SetupError::NoInvariants);
+        }
+        if let Some(name) = output
+            .targets
+            .iter()
+            .map(|(name, _)| name)
+            .duplicates()
+            .next()
+        {
+            return Err(SetupError::DuplicateTarget(name.clone()));
+        }
+        if !(0.0..=1.0).contains(&config.mutation_ratio) {
+            return Err(SetupError::InvalidMutationRatio(config.mutation_ratio));
+        }
+        if config.jobs == 0 {
+            return Err(SetupError::NoJobs);
+        }
+        let mut target_fuzzer = new_target_fuzzer(&config, &output.targets, config.seed)?;
+        // Deploy the invariant checker with random constructor arguments, if any.
+        let constructor_args = constructor(&output.invariant_checker.1)
+            .map(|constructor| target_fuzzer.generate_random_args(&constructor.inputs))
+            .transpose()
+            .map_err(SetupError::UnsupportedConstructorParam)?
+            .unwrap_or_default();
+        let mut runner = match config.spec_id {
+            Some(spec_id) => Evm::new_with_spec(spec_id),
+            None => Evm::default(),
+        };
+        let invariant_checker_address = deploy_invariant_checker(
+            &mut runner,
+            output.invariant_checker.0,
+            constructor_args.clone(),
+        )?;
+        let addresses = deploy_targets(
+            &mut runner,
+            invariant_checker_address,
+            &config.setup_function,
+            &config.targets_function,
+        )?;
+        let targets = match &output.targets[..] {
+            [(name, _)] => vec![(name.clone(), addresses)],
+            _ => {
+                if addresses.len() != output.targets.len() {
+                    return Err(SetupError::TargetCount(
+                        config.targets_function.clone(),
+                        addresses.len(),
+                    ));
+                }
+                output
+                    .targets
+                    .iter()
+                    .zip(addresses)
+                    .map(|((name, _), address)| (name.clone(), vec![address]))
+                    .collect()
+            }
+        };
+        let invariants = invariants
+            .into_iter()
+            .filter(|invariant| {
+                let mut runner = runner.clone();
+                match check_invariant(&mut runner, invariant_checker_address, invariant) {
+                    None | Some(Crash::BrokenInvariant(_)) => true,
+                    Some(crash) => {
+                        warn!("{crash} right after setup, skipping it");
+                        false
+                    }
+                }
+            })
+            .collect_vec();
+        if invariants.is_empty() && !config.panics {
+            return Err(SetupError::NoValidInvariants);
+        }
+        // Fund the senders so that they can send ether to payable functions.
+        for sender in &config.senders {
+            runner.set_balance(*sender, CALLER_BALANCE);
+        }
+        let deployment = Deployment {
+            setup_snapshot: runner.snapshot(),
+            runner,
+            targets,
+            invariant_checker_address,
+        };
+        deployment.configure(&mut target_fuzzer, &config.senders);
+        Ok(Self {
+            deployment,
+            config,
+            targets: output.targets,
+            invariants,
+            target_fuzzer,
+            constructor_args,
+        })
+    }
+
+    /// Return the generator of calls to the targets, e.g. to decode calls.
+    pub fn target_fuzzer(&self) -> &MultiTargetFuzzer {
+        &self.target_fuzzer
+    }
+
+    pub fn config(&self) -> &FuzzConfig {
+        &self.config
+    }
+
+    /// Return the invariants checked after each sequence of calls, in order.
+    pub fn invariants(&self) -> &[Invariant] {
+        &self.invariants
+    }
+
+    pub fn invariant_checker_address(&self) -> Address {
+        self.deployment.invariant_checker_address
+    }
+
+    /// Return the ABI-encoded arguments the invariant checker was deployed
+    /// with.
+    pub fn constructor_args(&self) -> &[u8] {
+        &self.constructor_args
+    }
+
+    /// Return `block.timestamp` right after `setUp()`.
+    pub fn block_timestamp(&self) -> u64 {
+        self.deployment.runner.block_timestamp()
+    }
+
+    /// Apply `calls` to the state right after `setUp()` and return the crash
+    /// they cause, if any.
+    pub fn replay(&self, calls: &[Call]) -> Option<Crash> {
+        let mut runner = self.deployment.runner.clone();
+        find_crash(
+            &mut runner,
+            &self.deployment,
+            &self.invariants,
+            calls,
+            self.config.panics,
+            None,
+            None,
+        )
+    }
+
+    /// Fuzz the target until the time or iteration limit is reached, or a
+    /// crash is found. Runs forever if neither limit is set and no crash is
+    /// found.
+    pub fn run(&mut self) -> FuzzResult {
+        let config = &self.config;
+        // The first worker keeps drawing from the RNG the constructor arguments
+        // were drawn from, the others are seeded from `seed` and their index.
+        let mut target_fuzzers = (1..config.jobs)
+            .map(|worker| {
+                // Fuzzer::new already built the first worker's.
+                let mut target_fuzzer =
+                    new_target_fuzzer(config, &self.targets, config.seed.wrapping_add(worker))
+                        .unwrap();
+                self.deployment
+                    .configure(&mut target_fuzzer, &config.senders);
+                target_fuzzer
+            })
+            .collect_vec();
+        let shared = Shared {
+            stop: AtomicBool::new(false),
+            iterations: AtomicU64::new(0),
+            deadline: config.duration.map(|duration| Instant::now() + duration),
+            progress: Mutex::new(Progress::new(config.jobs)),
+        };
+        let coverage = config.coverage.then(Coverage::default);
+        let results = thread::scope(|scope| {
+            let handles = std::iter::once(&mut self.target_fuzzer)
+                .chain(&mut target_fuzzers)
+                .zip(0..)
+                .map(|(target_fuzzer, worker)| {
+                    let coverage = coverage.as_ref().map(Coverage::share);
+                    let (deployment, invariants, shared) =
+                        (&self.deployment, &self.invariants, &shared);
+                    scope.spawn(move || {
+                        fuzz_worker(
+                            config,
+                            worker,
+                            deployment,
+                            invariants,
+                            target_fuzzer,
+                            coverage,
+                            shared,
+                        )
+                    })
+                })
+                .collect_vec();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect_vec()
+        });
+
+        let mut calls_per_function = BTreeMap::new();
+        for result in &results {
+            for (signature, calls) in &result.calls_per_function {
+                *calls_per_function.entry(signature.clone()).or_insert(0) += calls;
+            }
+        }
+        let gas_report = config.gas_report.then(|| {
+            let mut gas_report = BTreeMap::new();
+            for result in &results {
+                for (signature, stats) in &result.gas_report {
+                    gas_report
+                        .entry(signature.clone())
+                        .or_insert_with(GasStats::default)
+                        .merge(stats);
+                }
+            }
+            gas_report
+        });
+        let crash = results.into_iter().zip(0..).find_map(|(result, worker)| {
+            let (crash, iterations, calls, shrunk_calls) = result.crash?;
+            Some(FoundCrash {
+                crash,
+                iterations,
+                worker,
+                calls,
+                shrunk_calls,
+            })
+        });
+        let iterations = shared.iterations.into_inner();
+        FuzzResult {
+            iterations: config
+                .max_iterations
+                .map_or(iterations, |max| iterations.min(max)),
+            calls_per_function,
+            gas_report,
+            crash,
+        }
+    }
+}
+
+/// Create a fuzzer for the target contracts `targets`, given by name and
+/// ABI, configured from `config`, with seed `seed`. Contracts without
+/// functions left to call, e.g. with only `view` functions, are skipped.
+///
+/// Fails if no function is left to call, or if a weight names no target
+/// function.
+fn new_target_fuzzer(
+    config: &FuzzConfig,
+    targets: &[(String, Vec<ParsedFunction>)],
+    seed: u64,
+) -> Result<MultiTargetFuzzer, SetupError> {
+    let keep = |function: &FunctionSpec| {
+        (config.include.is_empty()
+            || config
+                .include
+                .iter()
+                .any(|pattern| pattern.matches(function.name())))
+            && !config
+                .exclude
+                .iter()
+                .any(|pattern| pattern.matches(function.name()))
+    };
+    let mut fuzzers = targets
+        .iter()
+        .zip(0..)
+        .filter_map(|((name, abi), idx)| {
+            // Keep the RNGs of the target contracts apart.
+            let seed = seed.wrapping_add(idx << 32);
+            let functions = FunctionSpec::from_abi(abi.clone())
+                .into_iter()
+                .filter(keep)
+                .collect_vec();
+            if functions.is_empty() {
+                return None;
+            }
+            let mut solidity_fuzzer =
+                SolidityFuzzer::with_functions(functions, seed, config.senders.clone());
+            solidity_fuzzer.set_max_time_delay(config.max_time_delay);
+            solidity_fuzzer.set_use_dictionary(config.dictionary);
+            solidity_fuzzer.set_mutation_ratio(config.mutation_ratio);
+            if let Some(corpus_dir) = &config.corpus_dir {
+                // Each target contract has its own corpus, in a subdirectory
+                // named after it if there are several.
+                let corpus_dir = match targets.len() {
+                    1 => corpus_dir.clone(),
+                    _ => corpus_dir.join(name.replace(['/', ':'], "_")),
+                };
+                solidity_fuzzer.set_corpus(Corpus::with_dir(corpus_dir));
+            }
+            Some((name.clone(), solidity_fuzzer))
+        })
+        .collect_vec();
+    for (name, weight) in &config.weights {
+        let mut found = false;
+        for (_, solidity_fuzzer) in &mut fuzzers {
+            if solidity_fuzzer.has_function(name) {
+                solidity_fuzzer.set_weight(name, *weight);
+                found = true;
+            }
+        }
+        if !found {
+            return Err(SetupError::UnknownWeight(name.clone()));
+        }
+    }
+    if fuzzers.is_empty() {
+        return Err(SetupError::NoFunctions);
+    }
+    Ok(MultiTargetFuzzer::new(fuzzers, !seed))
+}
+
+/// The deployed contracts, with the senders funded.
+struct Deployment {
+    runner: Evm,
+    /// State right after `setUp()`.
+    setup_snapshot: SnapshotId,
+    /// Name of each target contract along with the addresses of its
+    /// instances.
+    targets: Vec<(String, Vec<Address>)>,
+    invariant_checker_address: Address,
+}
+
+impl Deployment {
+    /// Let `target_fuzzer` send calls to the deployed targets, and draw
+    /// address arguments from the accounts taking part in the test: the zero
+    /// address, `senders`, the invariant checker and the targets.
+    fn configure(&self, target_fuzzer: &mut MultiTargetFuzzer, senders: &[Address]) {
+        for (name, addresses) in &self.targets {
+            target_fuzzer.set_targets(name, addresses.clone());
+        }
+        let known_addresses = std::iter::once(Address::ZERO)
+            .chain(senders.iter().copied())
+            .chain([self.invariant_checker_address])
+            .chain(
+                self.targets
+                    .iter()
+                    .flat_map(|(_, addresses)| addresses.clone()),
+            )
+            .unique()
+            .collect_vec();
+        target_fuzzer.set_known_addresses(known_addresses);
+    }
+}
+
+/// State shared by all workers.
+struct Shared {
+    /// Set by the first worker to find a crash to stop the others.
+    stop: AtomicBool,
+    /// Number of iterations started by all workers together.
+    iterations: AtomicU64,
+    /// Time at which all workers stop, if any.
+    deadline: Option<Instant>,
+    progress: Mutex<Progress>,
+}
+
+/// How often each worker publishes its statistics to [`Progress`].
+const PROGRESS_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of calls after which state accumulated across calls is reset to
+/// the state right after `setUp()`, which bounds the length of crashing call
+/// sequences to shrink and replay.
+pub const MAX_HISTORY_LEN: usize = 100;
+
+/// Statistics published by the workers for the periodic progress reports.
+struct Progress {
+    /// Time and iterations started by all workers at the last report.
+    last_report: (Instant, u64),
+    /// Calls to each target function and corpus size of each worker.
+    workers: Vec<(BTreeMap<String, u64>, usize)>,
+}
+
+impl Progress {
+    fn new(jobs: u64) -> Self {
+        Self {
+            last_report: (Instant::now(), 0),
+            workers: vec![Default::default(); jobs as usize],
+        }
+    }
+
+    /// Replace the statistics of `worker`, and log a progress report if the
+    /// last one is at least `interval` old.
+    fn update(
+        &mut self,
+        worker: usize,
+        calls_per_function: &BTreeMap<String, u64>,
+        corpus_len: usize,
+        iterations: u64,
+        coverage: Option<&Coverage>,
+        interval: Duration,
+    ) {
+        self.workers[worker] = (calls_per_function.clone(), corpus_len);
+        let (last_time, last_iterations) = self.last_report;
+        let elapsed = last_time.elapsed();
+        if elapsed < interval {
+            return;
+        }
+        self.last_report = (Instant::now(), iterations);
+
+        let rate = (iterations - last_iterations) as f64 / elapsed.as_secs_f64();
+        let corpus_len: usize = self.workers.iter().map(|(_, len)| len).sum();
+        info!("Tested {iterations} iterations without a crash ({rate:.0}/s)");
+        info!("Corpus of {corpus_len} calls");
+        if let Some(coverage) = coverage {
+            info!("Covered {} program counters", coverage.covered_count());
+        }
+        let mut calls_per_function = BTreeMap::new();
+        for (calls, _) in &self.workers {
+            for (label, calls) in calls {
+                *calls_per_function.entry(label).or_insert(0) += calls;
+            }
+        }
+        let total: u64 = calls_per_function.values().sum();
+        for (label, calls) in calls_per_function {
+            let share = 100.0 * calls as f64 / total as f64;
+            info!("  {label}: {calls} calls ({share:.1}%)");
+        }
+    }
+}
+
+/// Outcome of a single worker.
+struct WorkerResult {
+    calls_per_function: BTreeMap<String, u64>,
+    gas_report: BTreeMap<String, GasStats>,
+    /// The crash found, the iterations started when it was found, the
+    /// crashing calls and their shrunk version.
+    crash: Option<(Crash, u64, Vec<Call>, Vec<Call>)>,
+}
+
+/// Fuzz a copy of `deployment` with calls generated by `target_fuzzer`
+/// until the time or iteration limit is reached, this worker finds a crash,
+/// or another worker stops all of them.
+fn fuzz_worker(
+    config: &FuzzConfig,
+    worker: usize,
+    deployment: &Deployment,
+    invariants: &[Invariant],
+    target_fuzzer: &mut MultiTargetFuzzer,
+    mut coverage: Option<Coverage>,
+    shared: &Shared,
+) -> WorkerResult {
+    let mut runner = deployment.runner.clone();
+    let mut calls_per_function = BTreeMap::new();
+    let mut gas_report = BTreeMap::new();
+    let mut gas_used = vec![];
+    let mut next_progress_update = Instant::now() + PROGRESS_UPDATE_INTERVAL;
+    // Calls applied since the state was last reset to the state right after
+    // `setUp()`, which is where crashes are replayed from.
+    let mut history = vec![];
+    loop {
+        if shared.stop.load(Ordering::Relaxed)
+            || shared
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            break;
+        }
+        let iterations = shared.iterations.fetch_add(1, Ordering::Relaxed) + 1;
+        if config.max_iterations.is_some_and(|max| iterations > max) {
+            break;
+        }
+        let calls = (0..config.depth.unwrap_or(1))
+            .map(|_| target_fuzzer.generate_random_call())
+            .collect_vec();
+        for call in &calls {
+            let label = target_fuzzer.function_label(call).unwrap();
+            *calls_per_function.entry(label).or_insert(0u64) += 1;
+        }
+        if config.isolated || config.depth.is_some() || history.len() >= MAX_HISTORY_LEN {
+            runner.revert(&deployment.setup_snapshot);
+            history.clear();
+        }
+        history.extend(calls.iter().cloned());
+        gas_used.clear();
+        let crash = find_crash(
+            &mut runner,
+            deployment,
+            invariants,
+            &calls,
+            config.panics,
+            coverage.as_mut(),
+            config.gas_report.then_some(&mut gas_used),
+        );
+        for (call, gas_used) in calls.iter().zip(&gas_used) {
+            if let Some(gas_used) = gas_used {
+                let label = target_fuzzer.function_label(call).unwrap();
+                gas_report
+                    .entry(label)
+                    .or_insert_with(GasStats::default)
+                    .record(*gas_used);
+            }
+        }
+        // If a crash is detected, shrink it and stop all workers.
+        if let Some(crash) = crash {
+            if shared.stop.swap(true, Ordering::Relaxed) {
+                // Another worker found a crash first.
+                break;
+            }
+            let shrunk_calls = target_fuzzer.shrink(history.clone(), |calls| {
+                runner.revert(&deployment.setup_snapshot);
+                find_crash(
+                    &mut runner,
+                    deployment,
+                    invariants,
+                    calls,
+                    config.panics,
+                    None,
+                    None,
+                ) == Some(crash.clone())
+            });
+            return WorkerResult {
+                calls_per_function,
+                gas_report,
+                crash: Some((crash, iterations, history, shrunk_calls)),
+            };
+        }
+        if let Some(coverage) = &mut coverage {
+            for (target, calldata) in coverage.take_new_inputs() {
+                target_fuzzer.add_to_corpus(target, calldata);
+            }
+        }
+        if log_enabled!(Level::Info) && Instant::now() >= next_progress_update {
+            next_progress_update = Instant::now() + PROGRESS_UPDATE_INTERVAL;
+            shared.progress.lock().unwrap().update(
+                worker,
+                &calls_per_function,
+                target_fuzzer.corpus_len(),
+                shared.iterations.load(Ordering::Relaxed),
+                coverage.as_ref(),
+                config.stats_interval,
+            );
+        }
+    }
+    WorkerResult {
+        calls_per_function,
+        gas_report,
+        crash: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::compile_fixtures as compile;
+
+    #[test]
+    fn reverting_calls_are_not_crashes() {
+        let config = FuzzConfig {
+            include: vec![Pattern::new("fail").unwrap()],
+            max_iterations: Some(1000),
+            ..FuzzConfig::default()
+        };
+        let mut fuzzer = Fuzzer::new(compile(&["Reverter"], "ReverterTest"), config).unwrap();
+        let result = fuzzer.run();
+        assert!(result.crash.is_none());
+        assert_eq!(
+            result.calls_per_function["fail(uint256)"],
+            result.iterations
+        );
+    }
+
+    #[test]
+    fn targets_without_state_changing_functions_are_skipped() {
+        let abi = |name: &str, state_mutability: &str| -> Vec<ParsedFunction> {
+            serde_json::from_value(serde_json::json!([{
+                "type": "function",
+                "name": name,
+                "stateMutability": state_mutability,
+            }]))
+            .unwrap()
+        };
+        let targets = [
+            ("Viewer".to_owned(), abi("get", "view")),
+            ("Setter".to_owned(), abi("set", "nonpayable")),
+        ];
+        let mut target_fuzzer = new_target_fuzzer(&FuzzConfig::default(), &targets, 0).unwrap();
+        let setter = Address::with_last_byte(1);
+        target_fuzzer.set_targets("Setter", vec![setter]);
+        for _ in 0..100 {
+            assert_eq!(target_fuzzer.generate_random_call().target, setter);
+        }
+    }
+
+    #[test]
+    fn address_args_are_drawn_from_the_deployment() {
+        let abi = serde_json::from_value(serde_json::json!([{
+            "type": "function",
+            "name": "f",
+            "inputs": [{ "type": "address", "internalType": "address" }],
+            "stateMutability": "nonpayable",
+        }]))
+        .unwrap();
+        let targets = [("Target".to_owned(), abi)];
+        let mut target_fuzzer = new_target_fuzzer(&FuzzConfig::default(), &targets, 0).unwrap();
+        let runner = Evm::default();
+        let deployment = Deployment {
+            setup_snapshot: runner.snapshot(),
+            runner,
+            targets: vec![(
+                "Target".to_owned(),
+                vec![Address::with_last_byte(10), Address::with_last_byte(11)],
+            )],
+            invariant_checker_address: Address::with_last_byte(20),
+        };
+        let senders = [Address::with_last_byte(1), Address::with_last_byte(2)];
+        deployment.configure(&mut target_fuzzer, &senders);
+
+        let known = [0, 1, 2, 20, 10, 11].map(Address::with_last_byte);
+        let args = (0..10_000)
+            .map(|_| Address::from_slice(&target_fuzzer.generate_random_call().calldata[16..36]))
+            .collect_vec();
+        // Known with probability KNOWN_ADDRESS_PROBABILITY, that is 5000 times
+        // in 10000 on average, as random addresses hardly ever are.
+        let hits = args.iter().filter(|arg| known.contains(arg)).count();
+        assert!((4700..5300).contains(&hits), "{hits} known addresses");
+        for address in known {
+            assert!(args.contains(&address), "{address} is never drawn");
+        }
+    }
+
+    #[test]
+    fn unsupported_constructor_params_are_setup_errors() {
+        let mut output = compile(&["Reverter"], "ReverterTest");
+        output.invariant_checker.1.extend(
+            serde_json::from_value::<Vec<ParsedFunction>>(serde_json::json!([{
+                "type": "constructor",
+                "inputs": [{ "type": "function", "internalType": "function () external" }],
+                "stateMutability": "nonpayable",
+            }]))
+            .unwrap(),
+        );
+        let err = Fuzzer::new(output, FuzzConfig::default()).err().unwrap();
+        assert!(matches!(err, SetupError::UnsupportedConstructorParam(_)));
+    }
+
+    #[test]
+    fn gas_report_only_counts_successful_calls() {
+        let config = FuzzConfig {
+            isolated: true,
+            max_iterations: Some(1000),
+            gas_report: true,
+            ..FuzzConfig::default()
+        };
+        let mut fuzzer =
+            Fuzzer::new(compile(&["InvariantBreaker"], "InvariantTest"), config).unwrap();
+        let result = fuzzer.run();
+        let gas_report = result.gas_report.unwrap();
+        for (signature, calls) in &result.calls_per_function {
+            let stats = gas_report[signature];
+            assert_eq!(stats.calls, *calls);
+            assert!(stats.min <= stats.max);
+        }
+
+        let config = FuzzConfig {
+            include: vec![Pattern::new("fail").unwrap()],
+            max_iterations: Some(100),
+            gas_report: true,
+            ..FuzzConfig::default()
+        };
+        let mut fuzzer = Fuzzer::new(compile(&["Reverter"], "ReverterTest"), config).unwrap();
+        assert!(fuzzer.run().gas_report.unwrap().is_empty());
+    }
+
+    #[test]
+    fn crashes_replay_from_setup() {
+        let config = FuzzConfig {
+            max_iterations: Some(100_000),
+            ..FuzzConfig::default()
+        };
+        let mut fuzzer =
+            Fuzzer::new(compile(&["InvariantBreaker"], "InvariantTest"), config).unwrap();
+        let found = fuzzer.run().crash.unwrap();
+        let crash = Crash::BrokenInvariant("invariant_neverFalse()".to_owned());
+        assert_eq!(found.crash, crash);
+        assert!(found.calls.len() > 2);
+        assert_eq!(fuzzer.replay(&found.calls), Some(crash.clone()));
+        assert_eq!(fuzzer.replay(&found.shrunk_calls), Some(crash));
+        // Breaking the invariant takes both calls, in order.
+        let signatures = found
+            .shrunk_calls
+            .iter()
+            .map(|call| fuzzer.target_fuzzer().function(call).unwrap().signature())
+            .collect_vec();
+        assert_eq!(signatures, ["set0(uint8)", "set1(uint8)"]);
+    }
+
+    #[test]
+    fn invariants_report_odd_outputs() {
+        let output = compile(&["Reverter"], "OddInvariants");
+        let mut runner = Evm::default();
+        let address = runner.create(output.invariant_checker.0);
+        let crashes = invariant_functions(&output.invariant_checker.1)
+            .iter()
+            .map(|invariant| check_invariant(&mut runner, address, invariant))
+            .collect_vec();
+        let signature = |name: &str| format!("invariant_{name}()");
+        assert_eq!(
+            crashes,
+            [
+                None,
+                Some(Crash::BrokenInvariant(signature("false"))),
+                Some(Crash::InvalidInvariantOutput(signature("empty"), vec![])),
+                Some(Crash::InvalidInvariantOutput(
+                    signature("word"),
+                    vec![0xff; 32]
+                )),
+                Some(Crash::RevertedInvariant(signature("reverts"), vec![])),
+            ]
+        );
+    }
+
+    #[test]
+    fn halting_invariants_are_crashes() {
+        let mut runner = Evm::default();
+        // Return the runtime code INVALID, which halts on any call.
+        let address = runner.create(vec![
+            0x60, 1, 0x60, 12, 0x60, 0, 0x39, 0x60, 1, 0x60, 0, 0xf3, 0xfe,
+        ]);
+        let signature = "invariant_halts()".to_owned();
+        let invariant = Invariant {
+            selector: function_selector(&signature),
+            signature: signature.clone(),
+        };
+        let crash = check_invariant(&mut runner, address, &invariant).unwrap();
+        assert_eq!(
+            crash,
+            Crash::HaltedInvariant(signature, Halt::InvalidFEOpcode)
+        );
+        assert_eq!(
+            crash.to_string(),
+            "Invariant invariant_halts() halted: InvalidFEOpcode"
+        );
+    }
+
+    #[test]
+    fn odd_invariants_are_skipped_after_setup() {
+        let fuzzer = Fuzzer::new(
+            compile(&["Reverter"], "OddInvariants"),
+            FuzzConfig::default(),
+        )
+        .unwrap();
+        let signatures = fuzzer
+            .invariants()
+            .iter()
+            .map(|invariant| invariant.signature.as_str())
+            .collect_vec();
+        assert_eq!(signatures, ["invariant_true()", "invariant_false()"]);
+    }
+
+    #[test]
+    fn reverting_invariants_are_crashes() {
+        let config = FuzzConfig {
+            include: vec![Pattern::new("arm").unwrap()],
+            max_iterations: Some(10),
+            ..FuzzConfig::default()
+        };
+        let mut fuzzer = Fuzzer::new(compile(&["Reverter"], "ReverterTest"), config).unwrap();
+        let crash = fuzzer.run().crash.unwrap().crash;
+        let mut panic = function_selector("Panic(uint256)").to_vec();
+        panic.extend(U256::from(1).to_be_bytes::<32>());
+        assert_eq!(
+            crash,
+            Crash::RevertedInvariant("invariant_notArmed()".to_owned(), panic)
+        );
+        assert_eq!(
+            crash.to_string(),
+            "Invariant invariant_notArmed() reverted: Panic(0x1): assertion failed"
+        );
+    }
+}
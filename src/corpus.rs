@@ -0,0 +1,111 @@
+use std::fs;
+use std::path::PathBuf;
+
+use log::warn;
+use rand::Rng;
+use tiny_keccak::{Hasher, Keccak};
+
+/// Calldata of past calls worth mutating, optionally persisted to a
+/// directory so that fuzzing can resume across runs.
+#[derive(Default)]
+pub struct Corpus {
+    entries: Vec<Vec<u8>>,
+    /// Directory each new entry is saved to, if any.
+    dir: Option<PathBuf>,
+}
+
+impl Corpus {
+    /// Create a corpus persisted to `dir`, loading the entries saved there by
+    /// previous runs. The directory is created if it doesn't exist. Anything
+    /// in it but files is ignored, and files that can't be read or aren't
+    /// hex-encoded calldata are skipped with a warning.
+    ///
+    /// # Panics
+    /// Panics if `dir` can't be created or read.
+    pub fn with_dir(dir: PathBuf) -> Self {
+        fs::create_dir_all(&dir)
+            .unwrap_or_else(|err| panic!("Failed to create corpus {}: {err}", dir.display()));
+        let entries = fs::read_dir(&dir)
+            .unwrap_or_else(|err| panic!("Failed to read corpus {}: {err}", dir.display()))
+            .filter_map(|entry| {
+                let entry = entry
+                    .map_err(|err| warn!("Skipping corpus entry in {}: {err}", dir.display()))
+                    .ok()?;
+                if !entry.file_type().is_ok_and(|ty| ty.is_file()) {
+                    return None;
+                }
+                let path = entry.path();
+                let contents = fs::read_to_string(&path)
+                    .map_err(|err| warn!("Skipping corpus entry {}: {err}", path.display()))
+                    .ok()?;
+                hex::decode(contents.trim())
+                    .map_err(|err| warn!("Skipping invalid corpus entry {}: {err}", path.display()))
+                    .ok()
+            })
+            .collect();
+        Self {
+            entries,
+            dir: Some(dir),
+        }
+    }
+
+    /// Add `calldata` to the corpus, saving it to the corpus directory if
+    /// any. Entries already in the corpus are ignored.
+    pub fn add(&mut self, calldata: Vec<u8>) {
+        if self.entries.contains(&calldata) {
+            return;
+        }
+        if let Some(dir) = &self.dir {
+            // Name entries after their hash so that each is saved once.
+            let mut keccak = Keccak::v256();
+            let mut hash = [0u8; 32];
+            keccak.update(&calldata);
+            keccak.finalize(&mut hash);
+            fs::write(dir.join(hex::encode(&hash[..8])), hex::encode(&calldata)).unwrap();
+        }
+        self.entries.push(calldata);
+    }
+
+    /// Keep only the entries for which `keep` returns `true`, e.g. those that
+    /// are valid calls to the current target. Saved entries are not removed.
+    pub fn retain(&mut self, keep: impl FnMut(&Vec<u8>) -> bool) {
+        self.entries.retain(keep);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Return a random entry.
+    ///
+    /// # Panics
+    /// Panics if the corpus is empty.
+    pub fn random_entry(&self, rng: &mut impl Rng) -> &[u8] {
+        &self.entries[rng.gen_range(0..self.entries.len())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn with_dir_skips_invalid_entries() {
+        let dir = std::env::temp_dir().join(format!("simple_fuzzer_corpus_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut corpus = Corpus::with_dir(dir.clone());
+        corpus.add(vec![0x12, 0x34]);
+        corpus.add(vec![0x12, 0x34]);
+        fs::write(dir.join("not_hex"), "xyz").unwrap();
+        fs::write(dir.join("not_utf8"), [0xff, 0xfe]).unwrap();
+        fs::create_dir(dir.join("subdir")).unwrap();
+
+        let corpus = Corpus::with_dir(dir.clone());
+        assert_eq!(corpus.entries, [vec![0x12, 0x34]]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
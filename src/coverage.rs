@@ -0,0 +1,113 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use revm::{
+    interpreter::{InstructionResult, Interpreter},
+    primitives::{db::Database, Address},
+    EVMData, Inspector,
+};
+
+/// Inspector recording the program counters executed during a transaction,
+/// keyed by the address of the executing contract.
+#[derive(Default)]
+pub struct CoverageInspector {
+    covered: HashSet<(Address, usize)>,
+}
+
+impl<DB: Database> Inspector<DB> for CoverageInspector {
+    fn step(&mut self, interp: &mut Interpreter, _data: &mut EVMData<'_, DB>) -> InstructionResult {
+        self.covered
+            .insert((interp.contract.address, interp.program_counter()));
+        InstructionResult::Continue
+    }
+}
+
+/// Target and calldata of a call.
+type Input = (Address, Vec<u8>);
+
+/// Program counters executed over a whole fuzzing run, possibly shared
+/// between workers.
+#[derive(Default)]
+pub struct Coverage {
+    covered: Arc<Mutex<HashSet<(Address, usize)>>>,
+    /// Target and calldata of calls that reached new program counters, in
+    /// the order they were found by any of the coverages sharing them.
+    inputs: Arc<Mutex<Vec<Input>>>,
+    /// Number of `inputs` already taken by this coverage.
+    taken: usize,
+}
+
+impl Coverage {
+    /// Merge the program counters recorded by `inspector` during the call
+    /// to `target` with `calldata`, and keep `calldata` as a new input if
+    /// any of them wasn't covered yet. Returns whether there was new
+    /// coverage.
+    pub fn merge(
+        &mut self,
+        inspector: CoverageInspector,
+        target: Address,
+        calldata: &[u8],
+    ) -> bool {
+        let mut covered = self.covered.lock().unwrap();
+        let len = covered.len();
+        covered.extend(inspector.covered);
+        let is_new = covered.len() > len;
+        drop(covered);
+        if is_new {
+            self.inputs
+                .lock()
+                .unwrap()
+                .push((target, calldata.to_vec()));
+        }
+        is_new
+    }
+
+    /// Return the number of distinct program counters executed so far.
+    pub fn covered_count(&self) -> usize {
+        self.covered.lock().unwrap().len()
+    }
+
+    /// Return a coverage sharing the executed program counters and the
+    /// inputs reaching them with this one, e.g. for another worker, so that
+    /// each worker mutates the inputs found by all of them.
+    pub fn share(&self) -> Self {
+        Self {
+            covered: Arc::clone(&self.covered),
+            inputs: Arc::clone(&self.inputs),
+            taken: 0,
+        }
+    }
+
+    /// Take the target and calldata of calls that reached new coverage,
+    /// here or in a coverage sharing with this one, since the last call.
+    pub fn take_new_inputs(&mut self) -> Vec<Input> {
+        let inputs = self.inputs.lock().unwrap();
+        let new_inputs = inputs[self.taken..].to_vec();
+        self.taken = inputs.len();
+        new_inputs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_inputs_are_shared() {
+        let target = Address::with_last_byte(1);
+        let inspector = |pcs: &[usize]| CoverageInspector {
+            covered: pcs.iter().map(|&pc| (target, pc)).collect(),
+        };
+        let mut first = Coverage::default();
+        let mut second = first.share();
+        assert!(first.merge(inspector(&[0, 1]), target, &[1]));
+        assert!(!second.merge(inspector(&[1]), target, &[2]));
+        assert!(second.merge(inspector(&[2]), target, &[3]));
+
+        let expected = [(target, vec![1]), (target, vec![3])];
+        assert_eq!(first.take_new_inputs(), expected);
+        assert_eq!(second.take_new_inputs(), expected);
+        assert_eq!(first.take_new_inputs(), []);
+        assert_eq!(first.covered_count(), 3);
+    }
+}
@@ -1,12 +1,150 @@
+use std::convert::Infallible;
+use std::fmt;
+
+use log::debug;
 use revm::{
-    primitives::{Address, CreateScheme, ExecutionResult, Output, TransactTo, TxEnv},
-    InMemoryDB, EVM,
+    db::{AccountState, DatabaseRef},
+    primitives::{
+        Address, BlockEnv, Bytes, CreateScheme, EVMError, ExecutionResult, Log, Output, SpecId,
+        TransactTo, TxEnv, U256,
+    },
+    InMemoryDB, Inspector, EVM,
 };
 
+/// Sender of transactions unless another one is given explicitly.
+pub const DEFAULT_CALLER: Address = Address::ZERO;
+
+/// EVM versions as named by solc's `--evm-version`, with their [`SpecId`].
+pub const EVM_VERSIONS: [(&str, SpecId); 12] = [
+    ("homestead", SpecId::HOMESTEAD),
+    ("tangerineWhistle", SpecId::TANGERINE),
+    ("spuriousDragon", SpecId::SPURIOUS_DRAGON),
+    ("byzantium", SpecId::BYZANTIUM),
+    ("constantinople", SpecId::CONSTANTINOPLE),
+    ("petersburg", SpecId::PETERSBURG),
+    ("istanbul", SpecId::ISTANBUL),
+    ("berlin", SpecId::BERLIN),
+    ("london", SpecId::LONDON),
+    ("paris", SpecId::MERGE),
+    ("shanghai", SpecId::SHANGHAI),
+    ("cancun", SpecId::CANCUN),
+];
+
+/// Return the [`SpecId`] of the EVM version named `evm_version` by solc.
+pub fn spec_id(evm_version: &str) -> Option<SpecId> {
+    EVM_VERSIONS
+        .iter()
+        .find(|(name, _)| *name == evm_version)
+        .map(|(_, spec_id)| *spec_id)
+}
+
+#[derive(Clone)]
 pub struct Evm {
     evm: EVM<InMemoryDB>,
 }
 
+/// Reason a transaction reverted, decoded from its output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevertReason {
+    /// `Error(string)`, raised by `require` and `revert` with a message.
+    Error(String),
+    /// `Panic(uint256)`, raised by failing `assert`s, arithmetic overflows,
+    /// out-of-bounds accesses and similar.
+    Panic(U256),
+    /// Any other output, e.g. a custom error or an empty revert.
+    Other(Vec<u8>),
+}
+
+impl RevertReason {
+    /// Decode the output of a reverted transaction.
+    pub fn decode(output: &[u8]) -> Self {
+        match (output.len() >= 4).then(|| output.split_at(4)) {
+            Some((selector, data)) if selector == ERROR_SELECTOR => {
+                // The message is encoded as a single dynamic `string`.
+                let message = (|| {
+                    let offset: usize =
+                        U256::try_from_be_slice(data.get(..32)?)?.try_into().ok()?;
+                    let len_end = offset.checked_add(32)?;
+                    let len: usize = U256::try_from_be_slice(data.get(offset..len_end)?)?
+                        .try_into()
+                        .ok()?;
+                    let message = data.get(len_end..len_end.checked_add(len)?)?;
+                    Some(String::from_utf8_lossy(message).into_owned())
+                })();
+                match message {
+                    Some(message) => Self::Error(message),
+                    None => Self::Other(output.to_vec()),
+                }
+            }
+            Some((selector, data)) if selector == PANIC_SELECTOR && data.len() == 32 => {
+                Self::Panic(U256::from_be_slice(data))
+            }
+            _ => Self::Other(output.to_vec()),
+        }
+    }
+}
+
+impl fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RevertReason::Error(message) => write!(f, "Error({message:?})"),
+            RevertReason::Panic(code) => {
+                let description = match code.saturating_to::<u64>() {
+                    0x00 => "generic panic",
+                    0x01 => "assertion failed",
+                    0x11 => "arithmetic overflow or underflow",
+                    0x12 => "division or modulo by zero",
+                    0x21 => "invalid enum value",
+                    0x22 => "invalid storage byte array encoding",
+                    0x31 => "pop on empty array",
+                    0x32 => "array index out of bounds",
+                    0x41 => "too much memory allocated",
+                    0x51 => "call to zero-initialized internal function",
+                    _ => "unknown panic code",
+                };
+                write!(f, "Panic({code:#x}): {description}")
+            }
+            RevertReason::Other(output) if output.is_empty() => write!(f, "empty revert"),
+            RevertReason::Other(output) => write!(f, "0x{}", hex::encode(output)),
+        }
+    }
+}
+
+/// Error applying a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxError {
+    /// Execution reverted or halted with the given result.
+    Failed(ExecutionResult),
+    /// The transaction is invalid and wasn't executed, e.g. because the sender
+    /// can't afford the value sent.
+    Invalid(EVMError<Infallible>),
+}
+
+impl fmt::Display for TxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxError::Failed(ExecutionResult::Revert { output, .. }) => {
+                write!(f, "reverted: {}", RevertReason::decode(output))
+            }
+            TxError::Failed(ExecutionResult::Halt { reason, .. }) => {
+                write!(f, "halted: {reason:?}")
+            }
+            TxError::Failed(ExecutionResult::Success { .. }) => unreachable!(),
+            TxError::Invalid(err) => write!(f, "is invalid: {err}"),
+        }
+    }
+}
+
+/// Selector of `Error(string)`.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Selector of `Panic(uint256)`.
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Handle to a saved database and block environment, returned by
+/// [`Evm::snapshot`].
+pub struct SnapshotId(InMemoryDB, BlockEnv);
+
 impl Default for Evm {
     fn default() -> Self {
         Self {
@@ -19,6 +157,111 @@ impl Default for Evm {
 }
 
 impl Evm {
+    /// Create an EVM executing transactions according to hardfork `spec_id`.
+    pub fn new_with_spec(spec_id: SpecId) -> Self {
+        let mut evm = Self::default();
+        evm.evm.env.cfg.spec_id = spec_id;
+        evm
+    }
+
+    /// Capture the current database state and block environment.
+    ///
+    /// The returned snapshot is independent of later transactions and can be
+    /// reverted to any number of times. Dropping it releases the saved state.
+    pub fn snapshot(&self) -> SnapshotId {
+        SnapshotId(
+            self.evm.db.as_ref().unwrap().clone(),
+            self.evm.env.block.clone(),
+        )
+    }
+
+    /// Restore the database and block environment to the state captured by
+    /// `snapshot`, discarding the effects of all transactions applied since.
+    pub fn revert(&mut self, snapshot: &SnapshotId) {
+        self.evm.db = Some(snapshot.0.clone());
+        self.evm.env.block = snapshot.1.clone();
+    }
+
+    /// Return `block.timestamp` seen by subsequent transactions.
+    pub fn block_timestamp(&self) -> u64 {
+        self.evm.env.block.timestamp.saturating_to()
+    }
+
+    /// Set `block.timestamp` seen by subsequent transactions.
+    pub fn set_block_timestamp(&mut self, timestamp: u64) {
+        self.evm.env.block.timestamp = U256::from(timestamp);
+    }
+
+    /// Set `block.number` seen by subsequent transactions.
+    pub fn set_block_number(&mut self, number: u64) {
+        self.evm.env.block.number = U256::from(number);
+    }
+
+    /// Set `block.basefee` seen by subsequent transactions.
+    ///
+    /// Transactions pay a gas price equal to the base fee, so with a nonzero
+    /// base fee senders must be funded to cover their gas limit of `u64::MAX`.
+    pub fn set_basefee(&mut self, basefee: U256) {
+        self.evm.env.block.basefee = basefee;
+    }
+
+    /// Set the balance of the account at `address` to `balance`, creating the
+    /// account if it doesn't exist yet.
+    pub fn set_balance(&mut self, address: Address, balance: U256) {
+        let account = self.evm.db.as_mut().unwrap().load_account(address).unwrap();
+        if matches!(account.account_state, AccountState::NotExisting) {
+            account.account_state = AccountState::Touched;
+        }
+        account.info.balance = balance;
+    }
+
+    /// Return the balance of the account at `address`, zero if it doesn't
+    /// exist.
+    pub fn get_balance(&self, address: Address) -> U256 {
+        self.evm
+            .db
+            .as_ref()
+            .unwrap()
+            .accounts
+            .get(&address)
+            .and_then(|account| account.info())
+            .map_or(U256::ZERO, |info| info.balance)
+    }
+
+    /// Return the nonce of the account at `address`, zero if it doesn't
+    /// exist. The next contract created by the account is deployed at
+    /// `address.create(nonce)`.
+    pub fn get_nonce(&self, address: Address) -> u64 {
+        self.evm
+            .db
+            .as_ref()
+            .unwrap()
+            .accounts
+            .get(&address)
+            .and_then(|account| account.info())
+            .map_or(0, |info| info.nonce)
+    }
+
+    /// Set storage `slot` of the account at `address` to `value`.
+    pub fn set_storage(&mut self, address: Address, slot: U256, value: U256) {
+        self.evm
+            .db
+            .as_mut()
+            .unwrap()
+            .insert_account_storage(address, slot, value)
+            .unwrap();
+    }
+
+    /// Return the value of storage `slot` of the account at `address`.
+    pub fn get_storage(&self, address: Address, slot: U256) -> U256 {
+        self.evm
+            .db
+            .as_ref()
+            .unwrap()
+            .storage(address, slot)
+            .unwrap()
+    }
+
     /// Return code_size of given address.
     ///
     /// # Panics
@@ -38,7 +281,8 @@ impl Evm {
     /// # Panics
     /// Panics if execution reverts or halts unexpectedly.
     pub fn create(&mut self, bytecode: Vec<u8>) -> Address {
-        let (_, output) = self.transact_success_or_panic(TxEnv {
+        let (_, output, _) = self.transact_success_or_panic(TxEnv {
+            caller: DEFAULT_CALLER,
             gas_limit: u64::MAX,
             transact_to: TransactTo::Create(CreateScheme::Create),
             data: bytecode.into(),
@@ -50,28 +294,230 @@ impl Evm {
         }
     }
 
+    /// Apply create transaction with given `bytecode` as creation bytecode,
+    /// followed by ABI-encoded constructor arguments `encoded_args`.
+    /// Return created `address`.
+    ///
+    /// # Panics
+    /// Panics if execution reverts or halts unexpectedly.
+    pub fn create_with_args(&mut self, mut bytecode: Vec<u8>, encoded_args: Vec<u8>) -> Address {
+        bytecode.extend(encoded_args);
+        self.create(bytecode)
+    }
+
+    /// Apply create transaction with given `bytecode` as creation bytecode,
+    /// followed by ABI-encoded constructor arguments `encoded_args`.
+    /// Return created `address`, or a [`TxError`] if execution reverts or
+    /// halts.
+    pub fn try_create_with_args(
+        &mut self,
+        mut bytecode: Vec<u8>,
+        encoded_args: Vec<u8>,
+    ) -> Result<Address, TxError> {
+        bytecode.extend(encoded_args);
+        let (_, output, _) = self.transact(TxEnv {
+            caller: DEFAULT_CALLER,
+            gas_limit: u64::MAX,
+            transact_to: TransactTo::Create(CreateScheme::Create),
+            data: bytecode.into(),
+            ..Default::default()
+        })?;
+        match output {
+            Output::Create(_, Some(address)) => Ok(address),
+            _ => unreachable!(),
+        }
+    }
+
     /// Apply call transaction to given `address` with `calldata`.
     /// Returns `gas_used` and `return_data`.
     ///
     /// # Panics
     /// Panics if execution reverts or halts unexpectedly.
     pub fn call(&mut self, address: Address, calldata: Vec<u8>) -> (u64, Vec<u8>) {
-        let (gas_used, output) = self.transact_success_or_panic(TxEnv {
+        self.call_with_value(address, calldata, U256::ZERO)
+    }
+
+    /// Apply call transaction to given `address` with `calldata`, sending
+    /// `value` wei along. Returns `gas_used` and `return_data`.
+    ///
+    /// # Panics
+    /// Panics if execution reverts or halts unexpectedly, or if the caller
+    /// can't afford `value`.
+    pub fn call_with_value(
+        &mut self,
+        address: Address,
+        calldata: Vec<u8>,
+        value: U256,
+    ) -> (u64, Vec<u8>) {
+        let (gas_used, output, _) =
+            self.transact_success_or_panic(Self::call_tx(DEFAULT_CALLER, address, calldata, value));
+        match output {
+            Output::Call(output) => (gas_used, output.into()),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Apply call transaction to given `address` with `calldata`.
+    /// Returns `gas_used`, `return_data` and the logs emitted, e.g. to check
+    /// for events.
+    ///
+    /// # Panics
+    /// Panics if execution reverts or halts unexpectedly.
+    pub fn call_with_logs(
+        &mut self,
+        address: Address,
+        calldata: Vec<u8>,
+    ) -> (u64, Vec<u8>, Vec<Log>) {
+        let (gas_used, output, logs) = self.transact_success_or_panic(Self::call_tx(
+            DEFAULT_CALLER,
+            address,
+            calldata,
+            U256::ZERO,
+        ));
+        match output {
+            Output::Call(output) => (gas_used, output.into(), logs),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Apply call transaction from `sender` to given `address` with
+    /// `calldata`. Returns `gas_used` and `return_data`.
+    ///
+    /// # Panics
+    /// Panics if execution reverts or halts unexpectedly.
+    pub fn call_as(
+        &mut self,
+        sender: Address,
+        address: Address,
+        calldata: Vec<u8>,
+    ) -> (u64, Vec<u8>) {
+        let (gas_used, output, _) =
+            self.transact_success_or_panic(Self::call_tx(sender, address, calldata, U256::ZERO));
+        match output {
+            Output::Call(output) => (gas_used, output.into()),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Apply call transaction to given `address` with `calldata`.
+    /// Returns `gas_used` and `return_data`, or a [`TxError`] if execution
+    /// reverts or halts.
+    pub fn try_call(
+        &mut self,
+        address: Address,
+        calldata: Vec<u8>,
+    ) -> Result<(u64, Vec<u8>), TxError> {
+        self.try_call_with_value(address, calldata, U256::ZERO)
+    }
+
+    /// Apply call transaction to given `address` with `calldata`, sending
+    /// `value` wei along. Returns `gas_used` and `return_data`, or a
+    /// [`TxError`] if execution reverts or halts, or if the caller can't
+    /// afford `value`.
+    pub fn try_call_with_value(
+        &mut self,
+        address: Address,
+        calldata: Vec<u8>,
+        value: U256,
+    ) -> Result<(u64, Vec<u8>), TxError> {
+        self.try_call_as(DEFAULT_CALLER, address, calldata, value)
+    }
+
+    /// Apply call transaction from `sender` to given `address` with
+    /// `calldata`, sending `value` wei along. Returns `gas_used` and
+    /// `return_data`, or a [`TxError`] if execution reverts or halts, or if
+    /// `sender` can't afford `value`. A reverting or halting call leaves the
+    /// state as it was, except for the nonce and balance of `sender`, which
+    /// pays for the gas, and an invalid one isn't executed at all.
+    ///
+    /// `calldata` can be [`Bytes`] to avoid copying calldata that is sent
+    /// repeatedly.
+    pub fn try_call_as(
+        &mut self,
+        sender: Address,
+        address: Address,
+        calldata: impl Into<Bytes>,
+        value: U256,
+    ) -> Result<(u64, Vec<u8>), TxError> {
+        let (gas_used, output, _) =
+            self.transact(Self::call_tx(sender, address, calldata, value))?;
+        match output {
+            Output::Call(output) => Ok((gas_used, output.into())),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Same as [`Evm::try_call_as`], but execution is observed by
+    /// `inspector`, e.g. to record coverage.
+    pub fn try_call_as_with_inspector(
+        &mut self,
+        sender: Address,
+        address: Address,
+        calldata: impl Into<Bytes>,
+        value: U256,
+        inspector: impl Inspector<InMemoryDB>,
+    ) -> Result<(u64, Vec<u8>), TxError> {
+        let (gas_used, output, _) = self
+            .transact_with(Self::call_tx(sender, address, calldata, value), |evm| {
+                evm.inspect_commit(inspector)
+            })?;
+        match output {
+            Output::Call(output) => Ok((gas_used, output.into())),
+            _ => unreachable!(),
+        }
+    }
+
+    fn call_tx(
+        sender: Address,
+        address: Address,
+        calldata: impl Into<Bytes>,
+        value: U256,
+    ) -> TxEnv {
+        TxEnv {
+            caller: sender,
             gas_limit: u64::MAX,
             transact_to: TransactTo::Call(address),
             data: calldata.into(),
+            value,
             ..Default::default()
-        });
-        match output {
-            Output::Call(output) => (gas_used, output.into()),
-            _ => unreachable!(),
         }
     }
 
-    fn transact_success_or_panic(&mut self, tx: TxEnv) -> (u64, Output) {
-        self.evm.env.tx = tx;
-        let result = self.evm.transact_commit().unwrap();
+    fn transact_success_or_panic(&mut self, tx: TxEnv) -> (u64, Output, Vec<Log>) {
+        match self.transact(tx) {
+            Ok(result) => result,
+            Err(TxError::Failed(ExecutionResult::Revert { gas_used, output })) => panic!(
+                "Transaction reverts with gas_used {gas_used} and reason {}",
+                RevertReason::decode(&output)
+            ),
+            Err(TxError::Failed(ExecutionResult::Halt { reason, gas_used })) => panic!(
+                "Transaction halts unexpectedly with gas_used {gas_used} and reason {reason:?}"
+            ),
+            Err(TxError::Failed(ExecutionResult::Success { .. })) => unreachable!(),
+            Err(TxError::Invalid(err)) => panic!("Transaction is invalid: {err}"),
+        }
+    }
+
+    /// Apply `tx` and commit its changes. Returns `gas_used`, `output` and the
+    /// emitted logs if execution succeeds, or a [`TxError`] otherwise.
+    fn transact(&mut self, tx: TxEnv) -> Result<(u64, Output, Vec<Log>), TxError> {
+        self.transact_with(tx, |evm| evm.transact_commit())
+    }
+
+    /// Apply `tx` by calling `commit`, which executes the transaction set in
+    /// the environment and commits its changes. See [`Evm::transact`].
+    fn transact_with(
+        &mut self,
+        tx: TxEnv,
+        commit: impl FnOnce(&mut EVM<InMemoryDB>) -> Result<ExecutionResult, EVMError<Infallible>>,
+    ) -> Result<(u64, Output, Vec<Log>), TxError> {
+        self.evm.env.tx = TxEnv {
+            gas_price: self.evm.env.block.basefee,
+            ..tx
+        };
+        let result = commit(&mut self.evm);
         self.evm.env.tx = Default::default();
+        let result = result.map_err(TxError::Invalid)?;
         match result {
             ExecutionResult::Success {
                 gas_used,
@@ -79,24 +525,195 @@ impl Evm {
                 logs,
                 ..
             } => {
-                if !logs.is_empty() {
-                    println!("--- logs from {} ---", logs[0].address);
-                    for (log_idx, log) in logs.iter().enumerate() {
-                        println!("log#{log_idx}");
-                        for (topic_idx, topic) in log.topics.iter().enumerate() {
-                            println!("  topic{topic_idx}: {topic:?}");
-                        }
+                for (log_idx, log) in logs.iter().enumerate() {
+                    debug!("log#{log_idx} from {}", log.address);
+                    for (topic_idx, topic) in log.topics.iter().enumerate() {
+                        debug!("  topic{topic_idx}: {topic:?}");
                     }
-                    println!("--- end ---");
                 }
-                (gas_used, output)
-            }
-            ExecutionResult::Revert { gas_used, output } => {
-                panic!("Transaction reverts with gas_used {gas_used} and output {output:#x}")
+                Ok((gas_used, output, logs))
             }
-            ExecutionResult::Halt { reason, gas_used } => panic!(
-                "Transaction halts unexpectedly with gas_used {gas_used} and reason {reason:?}"
-            ),
+            result => Err(TxError::Failed(result)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use revm::primitives::keccak256;
+
+    use super::*;
+
+    /// Create a contract with `runtime` as its runtime bytecode.
+    fn deploy(evm: &mut Evm, runtime: &[u8]) -> Address {
+        let len = runtime.len() as u8;
+        // CODECOPY the runtime code following this 12-byte prefix and RETURN it.
+        let mut bytecode = vec![0x60, len, 0x60, 12, 0x60, 0, 0x39, 0x60, len, 0x60, 0, 0xf3];
+        bytecode.extend(runtime);
+        evm.create(bytecode)
+    }
+
+    #[test]
+    fn revert_restores_snapshot() {
+        let mut evm = Evm::default();
+        let address = Address::with_last_byte(1);
+        let slot = |slot: u64| U256::from(slot);
+        evm.set_storage(address, slot(1), U256::from(10));
+        evm.set_block_timestamp(100);
+        let snapshot = evm.snapshot();
+
+        evm.set_storage(address, slot(1), U256::from(20));
+        evm.set_storage(address, slot(2), U256::from(30));
+        evm.set_block_timestamp(200);
+        evm.revert(&snapshot);
+        assert_eq!(evm.get_storage(address, slot(1)), U256::from(10));
+        assert_eq!(evm.get_storage(address, slot(2)), U256::ZERO);
+        assert_eq!(evm.block_timestamp(), 100);
+
+        // The snapshot can be reverted to again.
+        evm.set_storage(address, slot(1), U256::from(40));
+        evm.revert(&snapshot);
+        assert_eq!(evm.get_storage(address, slot(1)), U256::from(10));
+    }
+
+    #[test]
+    fn try_call_returns_reverts() {
+        let mut evm = Evm::default();
+        // Revert with the calldata as the revert data.
+        let address = deploy(
+            &mut evm,
+            &[0x36, 0x60, 0, 0x60, 0, 0x37, 0x36, 0x60, 0, 0xfd],
+        );
+        let result = evm.try_call(address, vec![1, 2]);
+        let Err(TxError::Failed(ExecutionResult::Revert { output, .. })) = result else {
+            panic!("Expected a revert, got {result:?}");
+        };
+        assert_eq!(output.as_ref(), [1, 2]);
+    }
+
+    #[test]
+    fn set_balance_funds_value_transfers() {
+        let mut evm = Evm::default();
+        let recipient = Address::with_last_byte(1);
+        assert_eq!(evm.get_balance(DEFAULT_CALLER), U256::ZERO);
+        evm.set_balance(DEFAULT_CALLER, U256::from(1000));
+        assert_eq!(evm.get_balance(DEFAULT_CALLER), U256::from(1000));
+
+        evm.call_with_value(recipient, vec![], U256::from(300));
+        assert_eq!(evm.get_balance(DEFAULT_CALLER), U256::from(700));
+        assert_eq!(evm.get_balance(recipient), U256::from(300));
+    }
+
+    #[test]
+    fn unaffordable_values_are_invalid() {
+        let mut evm = Evm::default();
+        let recipient = Address::with_last_byte(1);
+        evm.set_balance(DEFAULT_CALLER, U256::from(100));
+        let result = evm.try_call_with_value(recipient, vec![], U256::from(101));
+        assert!(matches!(result, Err(TxError::Invalid(_))), "{result:?}");
+        // The transaction wasn't executed.
+        assert_eq!(evm.get_balance(DEFAULT_CALLER), U256::from(100));
+        assert_eq!(evm.get_nonce(DEFAULT_CALLER), 0);
+    }
+
+    #[test]
+    fn storage_round_trips() {
+        let mut evm = Evm::default();
+        // Return the value of the storage slot given as calldata.
+        let address = deploy(
+            &mut evm,
+            &[0x60, 0, 0x35, 0x54, 0x60, 0, 0x52, 0x60, 32, 0x60, 0, 0xf3],
+        );
+        // Slot of `balances[key]` for `mapping(address => uint256) balances`
+        // declared in slot 1.
+        let key = Address::with_last_byte(0xaa);
+        let mut preimage = key.into_word().to_vec();
+        preimage.extend(U256::from(1).to_be_bytes::<32>());
+        let mapping_slot = U256::from_be_bytes(keccak256(preimage).0);
+
+        for (slot, value) in [(U256::ZERO, U256::from(7)), (mapping_slot, U256::MAX)] {
+            assert_eq!(evm.get_storage(address, slot), U256::ZERO);
+            evm.set_storage(address, slot, value);
+            assert_eq!(evm.get_storage(address, slot), value);
+            let (_, output) = evm.call(address, slot.to_be_bytes::<32>().to_vec());
+            assert_eq!(output, value.to_be_bytes::<32>());
         }
     }
+
+    #[test]
+    fn block_timestamp_flows_into_calls() {
+        let mut evm = Evm::default();
+        // Return block.timestamp.
+        let address = deploy(&mut evm, &[0x42, 0x60, 0, 0x52, 0x60, 32, 0x60, 0, 0xf3]);
+        for timestamp in [1_700_000_000, u64::MAX] {
+            evm.set_block_timestamp(timestamp);
+            let (_, output) = evm.call(address, vec![]);
+            assert_eq!(output, U256::from(timestamp).to_be_bytes::<32>());
+        }
+    }
+
+    #[test]
+    fn creates_increment_the_nonce() {
+        let mut evm = Evm::default();
+        let nonce = evm.get_nonce(DEFAULT_CALLER);
+        let first = deploy(&mut evm, &[0x00]);
+        assert_eq!(first, DEFAULT_CALLER.create(nonce));
+        assert_eq!(evm.get_nonce(DEFAULT_CALLER), nonce + 1);
+        let second = deploy(&mut evm, &[0x00]);
+        assert_eq!(second, DEFAULT_CALLER.create(nonce + 1));
+        assert_eq!(evm.get_nonce(DEFAULT_CALLER), nonce + 2);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn revert_reasons_are_decoded() {
+        // `Error("no")`, as encoded by `revert("no")`.
+        let mut output = ERROR_SELECTOR.to_vec();
+        output.extend(U256::from(0x20).to_be_bytes::<32>());
+        output.extend(U256::from(2).to_be_bytes::<32>());
+        output.extend(b"no".iter().chain(&[0; 30]));
+        assert_eq!(
+            RevertReason::decode(&output),
+            RevertReason::Error("no".to_owned())
+        );
+        // A length past the end of the output isn't an `Error(string)`.
+        output[4 + 63] = 0xff;
+        assert!(matches!(
+            RevertReason::decode(&output),
+            RevertReason::Other(_)
+        ));
+
+        let mut output = PANIC_SELECTOR.to_vec();
+        output.extend(U256::from(0x11).to_be_bytes::<32>());
+        let reason = RevertReason::decode(&output);
+        assert_eq!(reason, RevertReason::Panic(U256::from(0x11)));
+        assert_eq!(
+            reason.to_string(),
+            "Panic(0x11): arithmetic overflow or underflow"
+        );
+
+        assert_eq!(RevertReason::decode(&[]).to_string(), "empty revert");
+    }
+
+    #[test]
+    fn reverted_calls_leave_storage_intact() {
+        let mut evm = Evm::default();
+        // Write 2 to slot 0, then revert if there is any calldata.
+        let address = deploy(
+            &mut evm,
+            &[
+                0x60, 2, 0x60, 0, 0x55, 0x36, 0x60, 10, 0x57, 0x00, 0x5b, 0x60, 0, 0x60, 0, 0xfd,
+            ],
+        );
+        evm.set_storage(address, U256::ZERO, U256::from(1));
+        let result = evm.try_call(address, vec![1]);
+        assert!(matches!(
+            result,
+            Err(TxError::Failed(ExecutionResult::Revert { .. }))
+        ));
+        assert_eq!(evm.get_storage(address, U256::ZERO), U256::from(1));
+
+        evm.try_call(address, vec![]).unwrap();
+        assert_eq!(evm.get_storage(address, U256::ZERO), U256::from(2));
+    }
 }
@@ -1,8 +1,18 @@
+use std::iter;
+use std::ops::Range;
+
 use itertools::Itertools;
-use rand::Rng;
+use log::{debug, warn};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    Rng, SeedableRng,
+};
+use revm::primitives::{Address, Bytes, U256};
 use tiny_keccak::{Hasher, Keccak};
 
-use crate::ParsedFunction;
+use crate::corpus::Corpus;
+use crate::solc::{ParsedFunction, ParsedParam};
 
 #[derive(Debug, Clone)]
 enum ParamKind {
@@ -14,6 +24,8 @@ enum ParamKind {
     Int(usize),
     /// Unsigned integer.
     Uint(usize),
+    /// Enum with this many variants, encoded as a `uint8`.
+    Enum(usize),
     /// Boolean.
     Bool,
     /// String.
@@ -29,75 +41,1074 @@ enum ParamKind {
 }
 
 impl ParamKind {
-    fn from_string(str: &str) -> Self {
+    fn from_param(param: &ParsedParam) -> Result<Self, String> {
+        Self::from_string(&param.ty, param)
+    }
+
+    /// Parse the ABI type `str` of `param`, whose components are the members
+    /// of the innermost tuple type, if any.
+    fn from_string(str: &str, param: &ParsedParam) -> Result<Self, String> {
+        // The last bracketed suffix is the outermost dimension, e.g.
+        // `uint256[2][]` is a dynamic array of `uint256[2]`.
+        if let Some((kind, size)) = str.strip_suffix(']').and_then(|str| str.rsplit_once('[')) {
+            let kind = Box::new(Self::from_string(kind, param)?);
+            return match size {
+                "" => Ok(Self::Array(kind)),
+                _ => match size.parse::<usize>() {
+                    Ok(size) if size > 0 => Ok(Self::FixedArray(kind, size)),
+                    _ => Err(format!("Invalid array size in parameter type '{str}'")),
+                },
+            };
+        }
         match str {
-            "address" => Self::Address,
-            "bytes" => Self::Bytes,
-            "uint8" => Self::Uint(8),
-            "uint16" => Self::Uint(16),
-            "uint32" => Self::Uint(32),
-            "uint64" => Self::Uint(64),
-            "uint128" => Self::Uint(128),
-            "uint256" => Self::Uint(256),
-            _ => unimplemented!(),
+            "address" => Ok(Self::Address),
+            "bytes" => Ok(Self::Bytes),
+            "bool" => Ok(Self::Bool),
+            "string" => Ok(Self::String),
+            "tuple" => Ok(Self::Tuple(
+                param
+                    .components
+                    .iter()
+                    .map(Self::from_param)
+                    .try_collect()?,
+            )),
+            "uint8" if param.enum_variants.is_some() => {
+                Ok(Self::Enum(param.enum_variants.unwrap()))
+            }
+            _ => {
+                if let Some(size) = str.strip_prefix("uint") {
+                    Ok(Self::Uint(parse_bit_width(str, size)?))
+                } else if let Some(size) = str.strip_prefix("int") {
+                    Ok(Self::Int(parse_bit_width(str, size)?))
+                } else if let Some(size) = str.strip_prefix("bytes") {
+                    match size.parse::<usize>() {
+                        Ok(size) if (1..=32).contains(&size) => Ok(Self::FixedBytes(size)),
+                        _ => Err(format!("Invalid size in parameter type '{str}'")),
+                    }
+                } else {
+                    Err(format!("Unsupported parameter type '{str}'"))
+                }
+            }
+        }
+    }
+
+    /// Canonical ABI name of this type as used in function signatures, e.g.
+    /// `(uint256,bytes)[]` for an array of structs.
+    fn type_name(&self) -> String {
+        match self {
+            ParamKind::Address => "address".to_string(),
+            ParamKind::Bytes => "bytes".to_string(),
+            ParamKind::Int(size) => format!("int{size}"),
+            ParamKind::Uint(size) => format!("uint{size}"),
+            ParamKind::Enum(_) => "uint8".to_string(),
+            ParamKind::Bool => "bool".to_string(),
+            ParamKind::String => "string".to_string(),
+            ParamKind::Array(kind) => format!("{}[]", kind.type_name()),
+            ParamKind::FixedBytes(size) => format!("bytes{size}"),
+            ParamKind::FixedArray(kind, size) => format!("{}[{size}]", kind.type_name()),
+            ParamKind::Tuple(kinds) => format!("({})", kinds.iter().map(Self::type_name).join(",")),
         }
     }
 
-    fn random(&self) -> Vec<u8> {
-        let mut rng = rand::thread_rng();
+    /// Whether values of this type are encoded in the tail of the enclosing
+    /// sequence, with only an offset stored in its head. This propagates
+    /// outwards, e.g. `uint256[3][2]` is static and encoded inline, but
+    /// `uint256[][2]` is dynamic.
+    fn is_dynamic(&self) -> bool {
+        match self {
+            ParamKind::Bytes | ParamKind::String | ParamKind::Array(_) => true,
+            // A fixed-size array is only dynamic if its elements are.
+            ParamKind::FixedArray(kind, _) => kind.is_dynamic(),
+            ParamKind::Tuple(kinds) => kinds.iter().any(ParamKind::is_dynamic),
+            _ => false,
+        }
+    }
+
+    /// Size of the head of a static value of this type.
+    fn head_len(&self) -> usize {
+        match self {
+            ParamKind::FixedArray(kind, size) => kind.head_len() * size,
+            ParamKind::Tuple(kinds) => kinds.iter().map(ParamKind::head_len).sum(),
+            _ => 32,
+        }
+    }
+
+    /// Generate a random value of this type. If `dictionary.boundaries` is
+    /// set, integers are drawn from [`uint_boundaries`] and
+    /// [`int_boundaries`] with probability [`DICTIONARY_PROBABILITY`].
+    /// Addresses are drawn from `dictionary.addresses`, if any, with
+    /// probability [`KNOWN_ADDRESS_PROBABILITY`]. Enums are out of range with
+    /// probability [`INVALID_ENUM_PROBABILITY`].
+    fn random(&self, rng: &mut impl Rng, dictionary: &Dictionary) -> ParamValue {
         match &self {
+            ParamKind::Enum(variants) => {
+                let mut output = [0u8; 32];
+                output[31] =
+                    if *variants <= u8::MAX as usize && rng.gen_bool(INVALID_ENUM_PROBABILITY) {
+                        rng.gen_range(*variants..=u8::MAX as usize) as u8
+                    } else {
+                        rng.gen_range(0..*variants) as u8
+                    };
+                ParamValue::Word(output)
+            }
+            ParamKind::Uint(size)
+                if dictionary.boundaries && rng.gen_bool(DICTIONARY_PROBABILITY) =>
+            {
+                let boundaries = uint_boundaries(*size);
+                ParamValue::Word(boundaries[rng.gen_range(0..boundaries.len())])
+            }
+            ParamKind::Int(size)
+                if dictionary.boundaries && rng.gen_bool(DICTIONARY_PROBABILITY) =>
+            {
+                let boundaries = int_boundaries(*size);
+                ParamValue::Word(boundaries[rng.gen_range(0..boundaries.len())])
+            }
             ParamKind::Uint(size) => {
-                let mut r = if *size == 8 {
-                    rng.gen::<[u8; 1]>().to_vec()
-                } else if *size == 16 {
-                    rng.gen::<[u8; 2]>().to_vec()
-                } else if *size == 32 {
-                    rng.gen::<[u8; 4]>().to_vec()
-                } else if *size == 64 {
-                    rng.gen::<[u8; 8]>().to_vec()
-                } else if *size == 128 {
-                    rng.gen::<[u8; 16]>().to_vec()
-                } else if *size == 256 {
-                    rng.gen::<[u8; 32]>().to_vec()
+                let mut output = [0u8; 32];
+                rng.fill(&mut output[32 - size / 8..]);
+                ParamValue::Word(output)
+            }
+            ParamKind::Address
+                if !dictionary.addresses.is_empty() && rng.gen_bool(KNOWN_ADDRESS_PROBABILITY) =>
+            {
+                dictionary.random_address(rng)
+            }
+            ParamKind::Address => {
+                let mut output = [0u8; 32];
+                rng.fill(&mut output[12..]);
+                ParamValue::Word(output)
+            }
+            ParamKind::Bool => {
+                // Pick `true` and `false` with equal probability so that both
+                // branches of a flag are exercised equally often.
+                let mut output = [0u8; 32];
+                output[31] = rng.gen_bool(0.5) as u8;
+                ParamValue::Word(output)
+            }
+            ParamKind::Int(size) => {
+                let mut output = [0u8; 32];
+                rng.fill(&mut output[32 - size / 8..]);
+                // Sign-extend the two's-complement value to a full word.
+                if output[32 - size / 8] & 0x80 != 0 {
+                    output[..32 - size / 8].fill(0xff);
+                }
+                ParamValue::Word(output)
+            }
+            ParamKind::Bytes => {
+                let mut data = vec![0u8; rng.gen_range(0..=MAX_DYNAMIC_LEN)];
+                rng.fill(data.as_mut_slice());
+                ParamValue::Bytes(data)
+            }
+            ParamKind::String => {
+                // Empty strings and multi-byte code points are common edge
+                // cases, so generate them more often than uniform sampling would.
+                let len = if rng.gen_ratio(1, 8) {
+                    0
                 } else {
-                    unreachable!()
+                    rng.gen_range(1..=MAX_DYNAMIC_LEN / 4)
                 };
-                let padded_bytes_len = 32 - size / 8;
-                let mut output = vec![0u8; padded_bytes_len];
-                output.append(&mut r);
-                output
+                let string = (0..len)
+                    .map(|_| {
+                        if rng.gen_ratio(1, 4) {
+                            rng.gen::<char>()
+                        } else {
+                            rng.gen_range(' '..='~')
+                        }
+                    })
+                    .collect::<String>();
+                ParamValue::Bytes(string.into_bytes())
             }
-            ParamKind::Address => {
-                let mut output = vec![0u8; 12];
-                output.extend_from_slice(&rng.gen::<[u8; 20]>());
-                output
+            ParamKind::FixedBytes(size) => {
+                // Unlike integers, fixed-size bytes are left-aligned.
+                let mut output = [0u8; 32];
+                rng.fill(&mut output[..*size]);
+                ParamValue::Word(output)
+            }
+            ParamKind::Array(kind) => {
+                let len = rng.gen_range(0..=MAX_ARRAY_LEN);
+                ParamValue::Array((0..len).map(|_| kind.random(rng, dictionary)).collect())
+            }
+            ParamKind::FixedArray(kind, size) => {
+                ParamValue::Tuple((0..*size).map(|_| kind.random(rng, dictionary)).collect())
+            }
+            ParamKind::Tuple(kinds) => ParamValue::Tuple(
+                kinds
+                    .iter()
+                    .map(|kind| kind.random(rng, dictionary))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Decode a value of this type from `data`, which starts at the encoding
+    /// of the value. Returns `None` if `data` is not a valid encoding.
+    fn decode(&self, data: &[u8]) -> Option<ParamValue> {
+        match self {
+            ParamKind::Bytes | ParamKind::String => {
+                let len = decode_usize(data)?;
+                Some(ParamValue::Bytes(
+                    data.get(32..len.checked_add(32)?)?.to_vec(),
+                ))
+            }
+            ParamKind::Array(kind) => {
+                let len = decode_usize(data)?;
+                let data = &data[32..];
+                // Every element takes up at least one word, which bounds the
+                // length of a well-formed array.
+                if len > data.len() / 32 {
+                    return None;
+                }
+                Some(ParamValue::Array(decode_sequence(
+                    std::iter::repeat(kind.as_ref()).take(len),
+                    data,
+                )?))
+            }
+            ParamKind::FixedArray(kind, size) => Some(ParamValue::Tuple(decode_sequence(
+                std::iter::repeat(kind.as_ref()).take(*size),
+                data,
+            )?)),
+            ParamKind::Tuple(kinds) => Some(ParamValue::Tuple(decode_sequence(kinds, data)?)),
+            _ => Some(ParamValue::Word(data.get(..32)?.try_into().unwrap())),
+        }
+    }
+}
+
+/// A value of some [`ParamKind`], holding just enough structure to be ABI-encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParamValue {
+    /// A single word: address, boolean, integer or fixed-size bytes.
+    Word([u8; 32]),
+    /// Bytes or UTF-8 encoded string.
+    Bytes(Vec<u8>),
+    /// Elements of a dynamic array.
+    Array(Vec<ParamValue>),
+    /// Elements of a fixed-size array or members of a tuple.
+    Tuple(Vec<ParamValue>),
+}
+
+impl ParamValue {
+    /// Whether this value is encoded in the tail of the enclosing sequence.
+    fn is_dynamic(&self) -> bool {
+        match self {
+            ParamValue::Word(_) => false,
+            ParamValue::Bytes(_) | ParamValue::Array(_) => true,
+            ParamValue::Tuple(values) => values.iter().any(ParamValue::is_dynamic),
+        }
+    }
+
+    /// Length of the ABI encoding of this value, see [`ParamValue::encode_into`].
+    fn encoded_len(&self) -> usize {
+        match self {
+            ParamValue::Word(_) => 32,
+            ParamValue::Bytes(data) => 32 + data.len().div_ceil(32) * 32,
+            ParamValue::Array(values) => 32 + sequence_len(values),
+            ParamValue::Tuple(values) => sequence_len(values),
+        }
+    }
+
+    /// Append the ABI encoding of this value to `output`. For dynamic values
+    /// this is the part that goes into the tail of the enclosing sequence.
+    fn encode_into(&self, output: &mut Vec<u8>) {
+        match self {
+            ParamValue::Word(word) => output.extend_from_slice(word),
+            ParamValue::Bytes(data) => {
+                output.extend_from_slice(&encode_word(data.len()));
+                output.extend_from_slice(data);
+                output.resize(output.len() + data.len().div_ceil(32) * 32 - data.len(), 0);
+            }
+            ParamValue::Array(values) => {
+                output.extend_from_slice(&encode_word(values.len()));
+                encode_sequence_into(values, output);
+            }
+            ParamValue::Tuple(values) => encode_sequence_into(values, output),
+        }
+    }
+
+    /// Format this value of type `kind` for humans, e.g. `0x1234...` for an
+    /// address and `-42` for a signed integer.
+    fn format(&self, kind: &ParamKind) -> String {
+        match (self, kind) {
+            (ParamValue::Word(word), ParamKind::Address) => {
+                format!("0x{}", hex::encode(&word[12..]))
+            }
+            (ParamValue::Word(word), ParamKind::Uint(_) | ParamKind::Enum(_)) => {
+                U256::from_be_bytes(*word).to_string()
+            }
+            (ParamValue::Word(word), ParamKind::Int(_)) => {
+                let value = U256::from_be_bytes(*word);
+                if word[0] & 0x80 != 0 {
+                    format!("-{}", value.wrapping_neg())
+                } else {
+                    value.to_string()
+                }
+            }
+            (ParamValue::Word(word), ParamKind::Bool) => (word[31] != 0).to_string(),
+            (ParamValue::Word(word), ParamKind::FixedBytes(size)) => {
+                format!("0x{}", hex::encode(&word[..*size]))
+            }
+            (ParamValue::Bytes(data), ParamKind::String) => {
+                format!("{:?}", String::from_utf8_lossy(data))
+            }
+            (ParamValue::Bytes(data), _) => format!("0x{}", hex::encode(data)),
+            (ParamValue::Array(values), ParamKind::Array(kind))
+            | (ParamValue::Tuple(values), ParamKind::FixedArray(kind, _)) => format!(
+                "[{}]",
+                values.iter().map(|value| value.format(kind)).join(", ")
+            ),
+            (ParamValue::Tuple(values), ParamKind::Tuple(kinds)) => format!(
+                "({})",
+                values
+                    .iter()
+                    .zip(kinds)
+                    .map(|(value, kind)| value.format(kind))
+                    .join(", ")
+            ),
+            _ => unreachable!("value doesn't match its kind"),
+        }
+    }
+
+    /// Return a Solidity expression evaluating to this value of type `kind`,
+    /// e.g. `uint8(42)`, or `None` for dynamic arrays, structs and enums,
+    /// which can't be written as literals.
+    fn to_solidity(&self, kind: &ParamKind) -> Option<String> {
+        Some(match (self, kind) {
+            (ParamValue::Word(word), ParamKind::Address) => {
+                Address::from_slice(&word[12..]).to_checksum(None)
+            }
+            (ParamValue::Word(_), ParamKind::Bool) => self.format(kind),
+            (
+                ParamValue::Word(_),
+                ParamKind::Uint(_) | ParamKind::Int(_) | ParamKind::FixedBytes(_),
+            ) => format!("{}({})", kind.type_name(), self.format(kind)),
+            (ParamValue::Bytes(data), ParamKind::String) => {
+                format!("string(hex\"{}\")", hex::encode(data))
+            }
+            (ParamValue::Bytes(data), _) => format!("bytes(hex\"{}\")", hex::encode(data)),
+            (ParamValue::Tuple(values), ParamKind::FixedArray(kind, _)) => format!(
+                "[{}]",
+                values
+                    .iter()
+                    .map(|value| value.to_solidity(kind))
+                    .collect::<Option<Vec<_>>>()?
+                    .join(", ")
+            ),
+            _ => return None,
+        })
+    }
+
+    /// Return values of type `kind` that are "smaller" than this one, roughly
+    /// in order of how much they simplify it.
+    fn shrink_candidates(&self, kind: &ParamKind) -> Vec<ParamValue> {
+        let mut candidates = vec![];
+        match (self, kind) {
+            (ParamValue::Word(word), _) if *word == [0u8; 32] => {}
+            (ParamValue::Word(word), ParamKind::Uint(_) | ParamKind::Enum(_)) => {
+                candidates.push(ParamValue::Word([0u8; 32]));
+                let half = U256::from_be_bytes(*word) >> 1usize;
+                candidates.push(ParamValue::Word(half.to_be_bytes()));
+            }
+            (ParamValue::Word(word), ParamKind::Int(_)) => {
+                candidates.push(ParamValue::Word([0u8; 32]));
+                // Halve towards zero, keeping the sign.
+                let value = U256::from_be_bytes(*word);
+                let half = if word[0] & 0x80 != 0 {
+                    (value.wrapping_neg() >> 1usize).wrapping_neg()
+                } else {
+                    value >> 1usize
+                };
+                candidates.push(ParamValue::Word(half.to_be_bytes()));
+            }
+            (ParamValue::Word(_), _) => candidates.push(ParamValue::Word([0u8; 32])),
+            (ParamValue::Bytes(data), _) => {
+                if !data.is_empty() {
+                    candidates.push(ParamValue::Bytes(vec![]));
+                    candidates.push(ParamValue::Bytes(data[..data.len() / 2].to_vec()));
+                    candidates.push(ParamValue::Bytes(data[..data.len() - 1].to_vec()));
+                }
+                if data.iter().any(|byte| *byte != 0) {
+                    candidates.push(ParamValue::Bytes(vec![0u8; data.len()]));
+                }
+            }
+            (ParamValue::Array(values), ParamKind::Array(kind)) => {
+                if !values.is_empty() {
+                    candidates.push(ParamValue::Array(vec![]));
+                    candidates.push(ParamValue::Array(values[..values.len() / 2].to_vec()));
+                    for idx in 0..values.len() {
+                        let mut values = values.clone();
+                        values.remove(idx);
+                        candidates.push(ParamValue::Array(values));
+                    }
+                }
+                candidates.extend(
+                    shrink_elements(values, std::iter::repeat(kind.as_ref()))
+                        .into_iter()
+                        .map(ParamValue::Array),
+                );
+            }
+            (ParamValue::Tuple(values), ParamKind::FixedArray(kind, _)) => candidates.extend(
+                shrink_elements(values, std::iter::repeat(kind.as_ref()))
+                    .into_iter()
+                    .map(ParamValue::Tuple),
+            ),
+            (ParamValue::Tuple(values), ParamKind::Tuple(kinds)) => candidates.extend(
+                shrink_elements(values, kinds)
+                    .into_iter()
+                    .map(ParamValue::Tuple),
+            ),
+            _ => unreachable!("value doesn't match its kind"),
+        }
+        candidates.dedup();
+        candidates.retain(|candidate| candidate != self);
+        candidates
+    }
+
+    /// Return a random variant of this value of type `kind`: a bit flip, a
+    /// value from `dictionary` for integers and addresses, a new value for
+    /// enums, or a length change for dynamic values. Elements of arrays and tuples are mutated
+    /// recursively.
+    fn mutate(&self, kind: &ParamKind, rng: &mut impl Rng, dictionary: &Dictionary) -> ParamValue {
+        match (self, kind) {
+            (ParamValue::Word(_), ParamKind::Uint(size))
+                if dictionary.boundaries && rng.gen_bool(0.5) =>
+            {
+                let boundaries = uint_boundaries(*size);
+                ParamValue::Word(boundaries[rng.gen_range(0..boundaries.len())])
+            }
+            (ParamValue::Word(_), ParamKind::Int(size))
+                if dictionary.boundaries && rng.gen_bool(0.5) =>
+            {
+                let boundaries = int_boundaries(*size);
+                ParamValue::Word(boundaries[rng.gen_range(0..boundaries.len())])
+            }
+            (ParamValue::Word(word), ParamKind::Uint(size)) => {
+                ParamValue::Word(flip_bit(*word, 32 - size / 8..32, rng))
+            }
+            (ParamValue::Word(word), ParamKind::Int(size)) => {
+                let mut output = flip_bit(*word, 32 - size / 8..32, rng);
+                // Keep the two's-complement value sign-extended to a full word.
+                let fill = if output[32 - size / 8] & 0x80 != 0 {
+                    0xff
+                } else {
+                    0
+                };
+                output[..32 - size / 8].fill(fill);
+                ParamValue::Word(output)
+            }
+            (ParamValue::Word(_), ParamKind::Address)
+                if !dictionary.addresses.is_empty() && rng.gen_bool(0.5) =>
+            {
+                dictionary.random_address(rng)
+            }
+            (ParamValue::Word(word), ParamKind::Address) => {
+                ParamValue::Word(flip_bit(*word, 12..32, rng))
+            }
+            (ParamValue::Word(_), ParamKind::Enum(_)) => kind.random(rng, dictionary),
+            (ParamValue::Word(word), ParamKind::Bool) => {
+                let mut output = *word;
+                output[31] ^= 1;
+                ParamValue::Word(output)
+            }
+            (ParamValue::Word(word), ParamKind::FixedBytes(size)) => {
+                ParamValue::Word(flip_bit(*word, 0..*size, rng))
+            }
+            (ParamValue::Bytes(data), _) => {
+                let mut data = data.clone();
+                match rng.gen_range(0..3) {
+                    0 if !data.is_empty() => {
+                        let idx = rng.gen_range(0..data.len());
+                        data[idx] ^= 1 << rng.gen_range(0..8);
+                    }
+                    1 if data.len() < MAX_DYNAMIC_LEN => {
+                        data.insert(rng.gen_range(0..=data.len()), rng.gen());
+                    }
+                    _ if !data.is_empty() => {
+                        data.remove(rng.gen_range(0..data.len()));
+                    }
+                    _ => data.push(rng.gen()),
+                }
+                ParamValue::Bytes(data)
+            }
+            (ParamValue::Array(values), ParamKind::Array(kind)) => {
+                let mut values = values.clone();
+                match rng.gen_range(0..3) {
+                    0 if !values.is_empty() => {
+                        let idx = rng.gen_range(0..values.len());
+                        values[idx] = values[idx].mutate(kind, rng, dictionary);
+                    }
+                    1 if values.len() < MAX_ARRAY_LEN => {
+                        let value = kind.random(rng, dictionary);
+                        values.insert(rng.gen_range(0..=values.len()), value);
+                    }
+                    _ if !values.is_empty() => {
+                        values.remove(rng.gen_range(0..values.len()));
+                    }
+                    _ => values.push(kind.random(rng, dictionary)),
+                }
+                ParamValue::Array(values)
+            }
+            (ParamValue::Tuple(values), ParamKind::FixedArray(kind, _)) if !values.is_empty() => {
+                let mut values = values.clone();
+                let idx = rng.gen_range(0..values.len());
+                values[idx] = values[idx].mutate(kind, rng, dictionary);
+                ParamValue::Tuple(values)
             }
-            ParamKind::Int(_)
-            | ParamKind::Bytes
-            | ParamKind::Bool
-            | ParamKind::String
-            | ParamKind::Array(_)
-            | ParamKind::FixedBytes(_)
-            | ParamKind::FixedArray(_, _)
-            | ParamKind::Tuple(_) => unimplemented!(),
+            (ParamValue::Tuple(values), ParamKind::Tuple(kinds)) if !values.is_empty() => {
+                let mut values = values.clone();
+                let idx = rng.gen_range(0..values.len());
+                values[idx] = values[idx].mutate(&kinds[idx], rng, dictionary);
+                ParamValue::Tuple(values)
+            }
+            (ParamValue::Tuple(_), _) => self.clone(),
+            _ => unreachable!("value doesn't match its kind"),
         }
     }
 }
 
+/// Return a copy of `word` with a random bit flipped in the byte range
+/// `bytes`.
+fn flip_bit(mut word: [u8; 32], bytes: Range<usize>, rng: &mut impl Rng) -> [u8; 32] {
+    word[rng.gen_range(bytes)] ^= 1 << rng.gen_range(0..8);
+    word
+}
+
+/// Return copies of `values` in which a single element has been replaced by
+/// one of its shrink candidates.
+fn shrink_elements<'a>(
+    values: &[ParamValue],
+    kinds: impl IntoIterator<Item = &'a ParamKind>,
+) -> Vec<Vec<ParamValue>> {
+    let mut candidates = vec![];
+    for (idx, (value, kind)) in values.iter().zip(kinds).enumerate() {
+        for candidate in value.shrink_candidates(kind) {
+            let mut values = values.to_vec();
+            values[idx] = candidate;
+            candidates.push(values);
+        }
+    }
+    candidates
+}
+
+/// Maximum length of randomly generated dynamic values.
+const MAX_DYNAMIC_LEN: usize = 128;
+
+/// Maximum number of elements of randomly generated dynamic arrays.
+const MAX_ARRAY_LEN: usize = 8;
+
+/// Probability of drawing a random integer from the dictionary of boundary
+/// values instead of generating uniformly random bytes.
+const DICTIONARY_PROBABILITY: f64 = 0.3;
+
+/// Probability of drawing a random address from the known addresses instead
+/// of generating uniformly random bytes, which would hardly ever hit an
+/// account taking part in the test.
+const KNOWN_ADDRESS_PROBABILITY: f64 = 0.5;
+
+/// Probability of generating an enum value past the last variant, to test
+/// that out-of-range values are rejected, instead of a valid one.
+const INVALID_ENUM_PROBABILITY: f64 = 0.05;
+
+/// Values to draw arguments from instead of random bytes some of the time.
+#[derive(Debug, Clone, Default)]
+struct Dictionary {
+    /// Draw integers from boundary values.
+    boundaries: bool,
+    /// Addresses of known accounts, e.g. the targets and senders.
+    addresses: Vec<Address>,
+}
+
+impl Dictionary {
+    /// Return a random one of the known addresses as a word.
+    ///
+    /// # Panics
+    /// Panics if there are no known addresses.
+    fn random_address(&self, rng: &mut impl Rng) -> ParamValue {
+        let address = self.addresses[rng.gen_range(0..self.addresses.len())];
+        let mut output = [0u8; 32];
+        output[12..].copy_from_slice(address.as_slice());
+        ParamValue::Word(output)
+    }
+}
+
+/// Common constants worth trying as integer arguments, e.g. one ether.
+const COMMON_CONSTANTS: [u64; 8] = [
+    2,
+    10,
+    100,
+    255,
+    256,
+    1000,
+    1_000_000,
+    1_000_000_000_000_000_000,
+];
+
+/// Interesting values of `uint<size>` as words: zero, one, the maximum and
+/// its neighbourhood, the midpoint and common constants that fit.
+pub fn uint_boundaries(size: usize) -> Vec<[u8; 32]> {
+    let max = U256::MAX >> (256 - size);
+    let half = U256::from(1) << (size - 1);
+    let mut values = vec![
+        U256::ZERO,
+        U256::from(1),
+        max,
+        max - U256::from(1),
+        half,
+        half - U256::from(1),
+    ];
+    values.extend(
+        COMMON_CONSTANTS
+            .iter()
+            .map(|&constant| U256::from(constant))
+            .filter(|&constant| constant <= max),
+    );
+    values
+        .into_iter()
+        .unique()
+        .map(|value| value.to_be_bytes())
+        .collect()
+}
+
+/// Interesting values of `int<size>` as sign-extended words: zero, plus and
+/// minus one, the minimum, the maximum and their neighbours, and common
+/// constants that fit.
+pub fn int_boundaries(size: usize) -> Vec<[u8; 32]> {
+    let max = (U256::from(1) << (size - 1)) - U256::from(1);
+    let min = max.wrapping_neg() - U256::from(1);
+    let mut values = vec![
+        U256::ZERO,
+        U256::from(1),
+        U256::from(1).wrapping_neg(),
+        max,
+        max - U256::from(1),
+        min,
+        min + U256::from(1),
+    ];
+    values.extend(
+        COMMON_CONSTANTS
+            .iter()
+            .map(|&constant| U256::from(constant))
+            .filter(|&constant| constant <= max)
+            .flat_map(|constant| [constant, constant.wrapping_neg()]),
+    );
+    values
+        .into_iter()
+        .unique()
+        .map(|value| value.to_be_bytes())
+        .collect()
+}
+
+/// Encode `value` as a 32-byte big-endian word.
+fn encode_word(value: usize) -> [u8; 32] {
+    U256::from(value).to_be_bytes()
+}
+
+/// Decode the first word of `data` as a `usize`, e.g. a length or offset.
+fn decode_usize(data: &[u8]) -> Option<usize> {
+    let word: [u8; 32] = data.get(..32)?.try_into().unwrap();
+    U256::from_be_bytes(word).try_into().ok()
+}
+
+/// Length of the head of the ABI encoding of `values` as a sequence, in which
+/// dynamic values take up a single offset word.
+fn head_len(values: &[ParamValue]) -> usize {
+    values
+        .iter()
+        .map(|value| {
+            if value.is_dynamic() {
+                32
+            } else {
+                value.encoded_len()
+            }
+        })
+        .sum()
+}
+
+/// Length of the ABI encoding of `values` as a sequence, head and tail.
+fn sequence_len(values: &[ParamValue]) -> usize {
+    values
+        .iter()
+        .map(|value| {
+            if value.is_dynamic() {
+                32 + value.encoded_len()
+            } else {
+                value.encoded_len()
+            }
+        })
+        .sum()
+}
+
+/// ABI-encode `values` as a sequence. Static values are placed inline in the
+/// head, while dynamic values are appended to the tail and referenced from the
+/// head by their offset from the sequence start.
+fn encode_sequence(values: &[ParamValue]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(sequence_len(values));
+    encode_sequence_into(values, &mut output);
+    output
+}
+
+/// Append the ABI encoding of `values` as a sequence to `output`, see
+/// [`encode_sequence`].
+fn encode_sequence_into(values: &[ParamValue], output: &mut Vec<u8>) {
+    let mut tail_offset = head_len(values);
+    for value in values {
+        if value.is_dynamic() {
+            output.extend_from_slice(&encode_word(tail_offset));
+            tail_offset += value.encoded_len();
+        } else {
+            value.encode_into(output);
+        }
+    }
+    for value in values.iter().filter(|value| value.is_dynamic()) {
+        value.encode_into(output);
+    }
+}
+
+/// ABI-encode a call to the function with `selector` with arguments
+/// `values`, allocating the calldata once.
+fn encode_call(selector: [u8; 4], values: &[ParamValue]) -> Vec<u8> {
+    let mut calldata = Vec::with_capacity(4 + sequence_len(values));
+    calldata.extend_from_slice(&selector);
+    encode_sequence_into(values, &mut calldata);
+    calldata
+}
+
+/// Decode a sequence of values of types `kinds` from `data`, the inverse of
+/// [`encode_sequence`].
+fn decode_sequence<'a>(
+    kinds: impl IntoIterator<Item = &'a ParamKind>,
+    data: &[u8],
+) -> Option<Vec<ParamValue>> {
+    let mut head_offset = 0;
+    kinds
+        .into_iter()
+        .map(|kind| {
+            let head = data.get(head_offset..)?;
+            if kind.is_dynamic() {
+                head_offset += 32;
+                kind.decode(data.get(decode_usize(head)?..)?)
+            } else {
+                head_offset += kind.head_len();
+                kind.decode(head)
+            }
+        })
+        .collect()
+}
+
+/// Parse the bit width `size` of an `intN`/`uintN` type named `ty`.
+/// Solidity only allows multiples of 8 between 8 and 256.
+fn parse_bit_width(ty: &str, size: &str) -> Result<usize, String> {
+    match size.parse::<usize>() {
+        Ok(size) if size % 8 == 0 && (8..=256).contains(&size) => Ok(size),
+        _ => Err(format!("Invalid bit width in parameter type '{ty}'")),
+    }
+}
+
+/// How calls to a target function are dispatched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FunctionKind {
+    /// A named function, called with its selector and ABI-encoded arguments.
+    Function,
+    /// `fallback()`, called with calldata not matching any selector. Modeled
+    /// as a function with a single `bytes` parameter making up the calldata.
+    Fallback,
+    /// `receive()`, called with empty calldata.
+    Receive,
+}
+
 /// Structure holding a function's signature information.
 #[derive(Debug, Clone)]
-struct FunctionSpec {
-    /// 4-byte function selector.
+pub struct FunctionSpec {
+    kind: FunctionKind,
+    /// 4-byte function selector, zero for `fallback()` and `receive()`.
     selector: [u8; 4],
     /// Parameter types for this function.
     params: Vec<ParamKind>,
     name: String,
+    /// Canonical signature, e.g. `transfer(address,uint256)`, which tells
+    /// overloaded functions apart.
+    signature: String,
+    /// Whether this function accepts ether.
+    payable: bool,
+    /// Relative probability of calling this function.
+    weight: u32,
+}
+
+/// A call to one of the target functions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    /// ABI-encoded calldata, including the selector. Cheap to clone, so that
+    /// it isn't copied each time the call is applied.
+    pub calldata: Bytes,
+    /// Amount of wei sent along with the call.
+    pub value: U256,
+    /// Sender of the call.
+    pub sender: Address,
+    /// Target contract the call is sent to.
+    pub target: Address,
+    /// Seconds to advance `block.timestamp` by before the call.
+    pub time_delay: u64,
+}
+
+impl FunctionSpec {
+    /// Return the functions in `abi` that can be fuzzed.
+    ///
+    /// `view` and `pure` functions are skipped since they can't change state.
+    /// `fallback()` and `receive()` are called with calldata matching no
+    /// other function and empty calldata respectively. Functions with
+    /// parameters of unsupported types are skipped with a warning.
+    pub fn from_abi(abi: Vec<ParsedFunction>) -> Vec<FunctionSpec> {
+        abi.into_iter()
+            .filter(|parsed_function| {
+                matches!(
+                    parsed_function.ty.as_str(),
+                    "function" | "fallback" | "receive"
+                ) && !matches!(parsed_function.state_mutability.as_str(), "view" | "pure")
+            })
+            .filter_map(|parsed_function| {
+                let payable = parsed_function.state_mutability == "payable";
+                let kind = match parsed_function.ty.as_str() {
+                    "fallback" => FunctionKind::Fallback,
+                    "receive" => FunctionKind::Receive,
+                    _ => FunctionKind::Function,
+                };
+                if kind != FunctionKind::Function {
+                    let name = parsed_function.ty;
+                    return Some(FunctionSpec {
+                        kind,
+                        selector: [0; 4],
+                        params: match kind {
+                            FunctionKind::Fallback => vec![ParamKind::Bytes],
+                            _ => vec![],
+                        },
+                        signature: format!("{name}()"),
+                        name,
+                        payable,
+                        weight: 1,
+                    });
+                }
+                let params = match parsed_function
+                    .inputs
+                    .iter()
+                    .map(ParamKind::from_param)
+                    .try_collect::<_, Vec<_>, _>()
+                {
+                    Ok(params) => params,
+                    Err(err) => {
+                        warn!("{err} in function '{}', skipping it", parsed_function.name);
+                        return None;
+                    }
+                };
+                // Selectors are computed over canonical ABI types, so e.g.
+                // a `contract IERC20` parameter is hashed as `address`.
+                let signature = parsed_function.name.clone()
+                    + "("
+                    + params.iter().map(ParamKind::type_name).join(",").as_str()
+                    + ")";
+                Some(FunctionSpec {
+                    kind,
+                    selector: function_selector(&signature),
+                    params,
+                    name: parsed_function.name,
+                    signature,
+                    payable,
+                    weight: 1,
+                })
+            })
+            .fold(Vec::<FunctionSpec>::new(), |mut functions, function| {
+                // Calls are dispatched by selector, so only the first of the
+                // functions sharing a selector can be called.
+                match functions.iter().find(|other| {
+                    other.kind == function.kind && other.selector == function.selector
+                }) {
+                    Some(other) if other.signature == function.signature => {}
+                    Some(other) => warn!(
+                        "Selector 0x{} of '{}' collides with '{}', skipping '{}'",
+                        hex::encode(function.selector),
+                        function.signature,
+                        other.signature,
+                        function.signature
+                    ),
+                    None => functions.push(function),
+                }
+                functions
+            })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+
+    pub fn selector(&self) -> [u8; 4] {
+        self.selector
+    }
+
+    /// Decode `calldata` as a call to this function and format it for humans,
+    /// e.g. `transfer(address=0x..., uint256=42)`. Returns `None` if
+    /// `calldata` is not a valid call to this function.
+    pub fn decode_call(&self, calldata: &[u8]) -> Option<String> {
+        Some(format!(
+            "{}({})",
+            self.name,
+            self.decode_args(calldata)?.join(", ")
+        ))
+    }
+
+    /// Decode the arguments of `calldata` as a call to this function and
+    /// format each for humans, e.g. `uint256=42`. Returns `None` if
+    /// `calldata` is not a valid call to this function.
+    pub fn decode_args(&self, calldata: &[u8]) -> Option<Vec<String>> {
+        let values = self.decode_values(calldata)?;
+        Some(
+            values
+                .iter()
+                .zip(&self.params)
+                .map(|(value, kind)| format!("{}={}", kind.type_name(), value.format(kind)))
+                .collect(),
+        )
+    }
+
+    /// Decode the arguments of `calldata` as a call to this function and
+    /// return each as a Solidity expression of its type, e.g. `uint8(42)`.
+    /// Returns `None` if `calldata` is not a valid call to this function, if
+    /// this is `fallback()` or `receive()`, or if an argument can't be
+    /// written as a literal, see [`ParamValue::to_solidity`].
+    pub fn solidity_args(&self, calldata: &[u8]) -> Option<Vec<String>> {
+        if self.kind != FunctionKind::Function {
+            return None;
+        }
+        self.decode_values(calldata)?
+            .iter()
+            .zip(&self.params)
+            .map(|(value, kind)| value.to_solidity(kind))
+            .collect()
+    }
+
+    /// Decode the argument values of `calldata` as a call to this function.
+    fn decode_values(&self, calldata: &[u8]) -> Option<Vec<ParamValue>> {
+        match self.kind {
+            FunctionKind::Function => {
+                if calldata.get(..4)? != self.selector {
+                    return None;
+                }
+                decode_sequence(&self.params, &calldata[4..])
+            }
+            FunctionKind::Fallback => Some(vec![ParamValue::Bytes(calldata.to_vec())]),
+            FunctionKind::Receive => calldata.is_empty().then(Vec::new),
+        }
+    }
+
+    /// Encode a call to this function with argument values `values`, the
+    /// inverse of [`FunctionSpec::decode_values`].
+    fn encode_values(&self, values: &[ParamValue]) -> Vec<u8> {
+        match (self.kind, values) {
+            (FunctionKind::Function, _) => encode_call(self.selector, values),
+            (FunctionKind::Fallback, [ParamValue::Bytes(data)]) => data.clone(),
+            (FunctionKind::Fallback, _) => unreachable!(),
+            (FunctionKind::Receive, _) => vec![],
+        }
+    }
+}
+
+/// Return the function of `functions` that `calldata` is dispatched to: the
+/// one with a matching selector, else `receive()` for empty calldata, else
+/// `fallback()`.
+fn dispatch<'a>(functions: &'a [FunctionSpec], calldata: &[u8]) -> Option<&'a FunctionSpec> {
+    let find = |kind| functions.iter().find(|function| function.kind == kind);
+    functions
+        .iter()
+        .find(|function| {
+            function.kind == FunctionKind::Function
+                && calldata.get(..4) == Some(function.selector.as_slice())
+        })
+        .or_else(|| calldata.is_empty().then(|| find(FunctionKind::Receive))?)
+        .or_else(|| find(FunctionKind::Fallback))
+}
+
+/// Encode a call to `function` with argument values `values`, or return
+/// `None` if it would be dispatched to another of `functions`, e.g. fallback
+/// calldata starting with a known selector.
+fn encode_dispatched(
+    functions: &[FunctionSpec],
+    function: &FunctionSpec,
+    values: &[ParamValue],
+) -> Option<Vec<u8>> {
+    let calldata = function.encode_values(values);
+    (dispatch(functions, &calldata)?.signature == function.signature).then_some(calldata)
 }
 
 /// --- Fuzzer Infrastructure ---
 pub struct SolidityFuzzer {
     /// target functions
     functions: Vec<FunctionSpec>,
+    /// Distribution of indices into `functions` by their weights.
+    function_index: WeightedIndex<u32>,
+    /// Source of randomness for generated inputs.
+    rng: StdRng,
+    /// Addresses calls are sent from.
+    senders: Vec<Address>,
+    /// Addresses of the target contracts calls are sent to.
+    targets: Vec<Address>,
+    /// Upper bound of the random delay before each call, in seconds.
+    max_time_delay: u64,
+    /// Boundary values and known addresses to draw arguments from.
+    dictionary: Dictionary,
+    /// Calldata of past calls worth mutating, e.g. because they reached new
+    /// coverage.
+    corpus: Corpus,
+    /// Probability of mutating a corpus entry instead of generating a fresh
+    /// call, if the corpus isn't empty.
+    mutation_ratio: f64,
+}
+
+/// Return the distribution of indices into `functions` by their weights.
+///
+/// # Panics
+/// Panics if `functions` is empty or all weights are zero.
+fn weighted_index(functions: &[FunctionSpec]) -> WeightedIndex<u32> {
+    WeightedIndex::new(functions.iter().map(|function| function.weight))
+        .unwrap_or_else(|err| panic!("No target functions to call: {err}"))
+}
+
+/// Shrink `calls` to a simpler sequence for which `breaks_invariant` still
+/// returns `true`, by dropping calls and replacing them with the simplified
+/// variants returned by `shrink_candidates`. See [`SolidityFuzzer::shrink`].
+fn shrink_calls(
+    mut calls: Vec<Call>,
+    shrink_candidates: impl Fn(&Call) -> Vec<Call>,
+    mut breaks_invariant: impl FnMut(&[Call]) -> bool,
+) -> Vec<Call> {
+    'shrink: loop {
+        // Drop runs of calls, from half of them down to single calls, so that
+        // long sequences shrink in few steps.
+        let run_lens = iter::successors(Some(calls.len() / 2), |&len| Some(len / 2))
+            .take_while(|&len| len > 0);
+        let dropped_calls = run_lens.flat_map(|run_len| {
+            let calls = &calls;
+            (0..calls.len()).step_by(run_len).map(move |start| {
+                let mut calls = calls.clone();
+                calls.drain(start..(start + run_len).min(calls.len()));
+                calls
+            })
+        });
+        let shrunk_calls = (0..calls.len()).flat_map(|idx| {
+            let calls = &calls;
+            shrink_candidates(&calls[idx]).into_iter().map(move |call| {
+                let mut calls = calls.clone();
+                calls[idx] = call;
+                calls
+            })
+        });
+        for candidate in dropped_calls.chain(shrunk_calls).collect_vec() {
+            if breaks_invariant(&candidate) {
+                calls = candidate;
+                continue 'shrink;
+            }
+        }
+        return calls;
+    }
 }
 
 pub fn function_selector(signature: &str) -> [u8; 4] {
@@ -109,40 +1120,1027 @@ pub fn function_selector(signature: &str) -> [u8; 4] {
 }
 
 impl SolidityFuzzer {
-    pub fn new(abi: Vec<ParsedFunction>) -> Self {
+    /// Create a fuzzer for the functions in `abi` picked by
+    /// [`FunctionSpec::from_abi`], generating inputs from an RNG seeded with
+    /// `seed` so that runs are reproducible. Calls are sent from randomly
+    /// chosen `senders`.
+    ///
+    /// # Panics
+    /// Panics if `senders` is empty or `abi` has no state-changing functions.
+    pub fn new(abi: Vec<ParsedFunction>, seed: u64, senders: Vec<Address>) -> Self {
+        Self::with_functions(FunctionSpec::from_abi(abi), seed, senders)
+    }
+
+    /// Create a fuzzer like [`SolidityFuzzer::new`] calling `functions`.
+    ///
+    /// # Panics
+    /// Panics if `senders` or `functions` is empty.
+    pub fn with_functions(functions: Vec<FunctionSpec>, seed: u64, senders: Vec<Address>) -> Self {
+        assert!(!senders.is_empty(), "At least one sender is required");
         Self {
-            functions: abi
+            function_index: weighted_index(&functions),
+            functions,
+            rng: StdRng::seed_from_u64(seed),
+            senders,
+            targets: vec![],
+            max_time_delay: 0,
+            dictionary: Dictionary::default(),
+            corpus: Corpus::default(),
+            mutation_ratio: 0.5,
+        }
+    }
+
+    /// Pick a random target function and generate calldata calling it with
+    /// random arguments. Returns the chosen function along with the calldata.
+    ///
+    /// If the corpus isn't empty, a random corpus entry is mutated instead
+    /// with probability set by [`SolidityFuzzer::set_mutation_ratio`].
+    pub fn generate_random_calldata(&mut self) -> (&FunctionSpec, Vec<u8>) {
+        if !self.corpus.is_empty() && self.rng.gen_bool(self.mutation_ratio) {
+            let entry = self.corpus.random_entry(&mut self.rng).to_vec();
+            let calldata = self.mutate(&entry);
+            let function = self.function(&calldata).unwrap();
+            debug!("Call function {} with input {:?}", function.name, calldata);
+            return (function, calldata);
+        }
+        loop {
+            let function = &self.functions[self.function_index.sample(&mut self.rng)];
+            let values = function
+                .params
+                .iter()
+                .map(|p| p.random(&mut self.rng, &self.dictionary))
+                .collect_vec();
+            // Fallback calldata dispatched to another function is drawn again.
+            if let Some(calldata) = encode_dispatched(&self.functions, function, &values) {
+                debug!("Call function {} with input {:?}", function.name, calldata);
+                return (function, calldata);
+            }
+        }
+    }
+
+    /// Return a variant of `calldata`, a call to one of the target
+    /// functions, with a random argument mutated: bits flipped, replaced by
+    /// a boundary value if the dictionary is enabled, or resized if dynamic.
+    ///
+    /// # Panics
+    /// Panics if `calldata` is not a valid call to a target function.
+    pub fn mutate(&mut self, calldata: &[u8]) -> Vec<u8> {
+        let function = dispatch(&self.functions, calldata)
+            .expect("Calldata is not a call to a target function");
+        let mut values = function.decode_values(calldata).unwrap();
+        if !values.is_empty() {
+            let idx = self.rng.gen_range(0..values.len());
+            values[idx] =
+                values[idx].mutate(&function.params[idx], &mut self.rng, &self.dictionary);
+        }
+        // Keep fallback calldata that would be dispatched to another function.
+        encode_dispatched(&self.functions, function, &values).unwrap_or_else(|| calldata.to_vec())
+    }
+
+    /// Add `calldata`, a call to one of the target functions, to the corpus
+    /// of inputs to mutate.
+    ///
+    /// # Panics
+    /// Panics if `calldata` is not a valid call to a target function.
+    pub fn add_to_corpus(&mut self, calldata: Vec<u8>) {
+        assert!(
+            self.decode_call(&calldata).is_some(),
+            "Corpus entry is not a valid call to a target function"
+        );
+        self.corpus.add(calldata);
+    }
+
+    /// Replace the corpus of inputs to mutate with `corpus`, e.g. one loaded
+    /// from a previous run. Entries that are not valid calls to the target
+    /// functions are dropped.
+    pub fn set_corpus(&mut self, mut corpus: Corpus) {
+        corpus.retain(|calldata| self.decode_call(calldata).is_some());
+        self.corpus = corpus;
+    }
+
+    /// Return the number of entries in the corpus of inputs to mutate.
+    pub fn corpus_len(&self) -> usize {
+        self.corpus.len()
+    }
+
+    /// Mutate a corpus entry instead of generating a fresh call with
+    /// probability `mutation_ratio`, if the corpus isn't empty. Defaults to
+    /// `0.5`.
+    ///
+    /// # Panics
+    /// Panics if `mutation_ratio` is not in `0.0..=1.0`.
+    pub fn set_mutation_ratio(&mut self, mutation_ratio: f64) {
+        assert!(
+            (0.0..=1.0).contains(&mutation_ratio),
+            "Mutation ratio must be between 0 and 1"
+        );
+        self.mutation_ratio = mutation_ratio;
+    }
+
+    /// Keep only the target functions for which `keep` returns `true`.
+    ///
+    /// # Panics
+    /// Panics if no function is left.
+    pub fn retain_functions(&mut self, keep: impl FnMut(&FunctionSpec) -> bool) {
+        self.functions.retain(keep);
+        self.function_index = weighted_index(&self.functions);
+    }
+
+    /// Call the target functions named `name` with relative probability
+    /// `weight`. All functions have weight 1 by default. `name` can also be
+    /// the signature of a single overload, e.g. `foo(uint256)`.
+    ///
+    /// # Panics
+    /// Panics if there is no target function named `name`, or if all weights
+    /// would be zero.
+    pub fn set_weight(&mut self, name: &str, weight: u32) {
+        assert!(self.has_function(name), "No target function named '{name}'");
+        for function in self
+            .functions
+            .iter_mut()
+            .filter(|function| function.name == name || function.signature == name)
+        {
+            function.weight = weight;
+        }
+        self.function_index = weighted_index(&self.functions);
+    }
+
+    /// Return whether there is a target function named `name`, or with
+    /// signature `name`.
+    pub fn has_function(&self, name: &str) -> bool {
+        self.functions
+            .iter()
+            .any(|function| function.name == name || function.signature == name)
+    }
+
+    /// Return the target functions.
+    pub fn functions(&self) -> &[FunctionSpec] {
+        &self.functions
+    }
+
+    /// Send calls to a random one of `targets`, contracts sharing the target
+    /// functions. Must be set before generating calls.
+    pub fn set_targets(&mut self, targets: Vec<Address>) {
+        self.targets = targets;
+    }
+
+    /// Return the addresses calls are sent to.
+    pub fn targets(&self) -> &[Address] {
+        &self.targets
+    }
+
+    /// Let calls advance `block.timestamp` by a random delay of at most
+    /// `max_time_delay` seconds. Delays are disabled by default.
+    pub fn set_max_time_delay(&mut self, max_time_delay: u64) {
+        self.max_time_delay = max_time_delay;
+    }
+
+    /// Draw integer arguments from a dictionary of boundary values such as
+    /// `0` and `type(uint256).max` some of the time. Disabled by default.
+    pub fn set_use_dictionary(&mut self, use_dictionary: bool) {
+        self.dictionary.boundaries = use_dictionary;
+    }
+
+    /// Draw address arguments from `addresses`, e.g. the targets and
+    /// senders, half of the time instead of generating random ones. None
+    /// are known by default.
+    pub fn set_known_addresses(&mut self, addresses: Vec<Address>) {
+        self.dictionary.addresses = addresses;
+    }
+
+    /// Generate a random call to one of the target functions of a random
+    /// target from a random sender. Payable functions are sent a random
+    /// amount of wei half of the time, `receive()` always.
+    ///
+    /// # Panics
+    /// Panics if no targets are set, see [`SolidityFuzzer::set_targets`].
+    pub fn generate_random_call(&mut self) -> Call {
+        assert!(!self.targets.is_empty(), "No targets to call");
+        let (function, calldata) = self.generate_random_calldata();
+        let value = if function.kind == FunctionKind::Receive
+            || function.payable && self.rng.gen_bool(0.5)
+        {
+            U256::from(self.rng.gen::<u64>())
+        } else {
+            U256::ZERO
+        };
+        let sender = self.senders[self.rng.gen_range(0..self.senders.len())];
+        let target = self.targets[self.rng.gen_range(0..self.targets.len())];
+        let time_delay = self.rng.gen_range(0..=self.max_time_delay);
+        Call {
+            calldata: calldata.into(),
+            value,
+            sender,
+            target,
+            time_delay,
+        }
+    }
+
+    /// Generate ABI-encoded random arguments for parameters `params`, e.g. to
+    /// pass to a constructor. Fails if a parameter has an unsupported type.
+    pub fn generate_random_args(&mut self, params: &[ParsedParam]) -> Result<Vec<u8>, String> {
+        let values = params
+            .iter()
+            .map(|p| {
+                ParamKind::from_param(p).map(|kind| kind.random(&mut self.rng, &self.dictionary))
+            })
+            .try_collect::<_, Vec<_>, _>()?;
+        Ok(encode_sequence(&values))
+    }
+
+    /// Return the target function `calldata` calls, if any.
+    pub fn function(&self, calldata: &[u8]) -> Option<&FunctionSpec> {
+        dispatch(&self.functions, calldata)
+    }
+
+    /// Decode `calldata` as a call to one of the target functions and format
+    /// it for humans. See [`FunctionSpec::decode_call`].
+    pub fn decode_call(&self, calldata: &[u8]) -> Option<String> {
+        self.function(calldata)?.decode_call(calldata)
+    }
+
+    /// Shrink `calls`, a sequence of calls to the target functions that is
+    /// known to break the invariant, to a simpler sequence for which
+    /// `breaks_invariant` still returns `true`.
+    ///
+    /// Candidates are produced by dropping runs of calls from the sequence,
+    /// halving their length down to single calls, then by zeroing words and
+    /// bytes, halving integers and shortening dynamic values one argument at
+    /// a time. The first candidate that still breaks the invariant is kept
+    /// until none does.
+    pub fn shrink(
+        &self,
+        calls: Vec<Call>,
+        breaks_invariant: impl FnMut(&[Call]) -> bool,
+    ) -> Vec<Call> {
+        shrink_calls(calls, |call| self.shrink_candidates(call), breaks_invariant)
+    }
+
+    /// Return simplified variants of `call`. Its arguments are only shrunk if
+    /// its calldata can be decoded as a call to one of the target functions.
+    fn shrink_candidates(&self, call: &Call) -> Vec<Call> {
+        let mut candidates = vec![];
+        if call.sender != self.senders[0] {
+            candidates.push(Call {
+                sender: self.senders[0],
+                ..call.clone()
+            });
+        }
+        if let Some(&target) = self
+            .targets
+            .first()
+            .filter(|&&target| target != call.target)
+        {
+            candidates.push(Call {
+                target,
+                ..call.clone()
+            });
+        }
+        if call.time_delay != 0 {
+            for time_delay in [0, call.time_delay / 2] {
+                candidates.push(Call {
+                    time_delay,
+                    ..call.clone()
+                });
+            }
+        }
+        if call.value != U256::ZERO {
+            for value in [U256::ZERO, call.value >> 1usize] {
+                candidates.push(Call {
+                    value,
+                    ..call.clone()
+                });
+            }
+        }
+        let Some(function) = self.function(&call.calldata) else {
+            return candidates;
+        };
+        let Some(values) = function.decode_values(&call.calldata) else {
+            return candidates;
+        };
+        candidates.extend(
+            shrink_elements(&values, &function.params)
                 .into_iter()
-                .map(|parsed_function| {
-                    let param_type = parsed_function
-                        .inputs
-                        .into_iter()
-                        .map(|p| p.internal_type)
-                        .collect_vec();
-                    let signature =
-                        parsed_function.name.clone() + "(" + param_type.join(",").as_str() + ")";
-                    FunctionSpec {
-                        selector: function_selector(&signature),
-                        params: param_type
-                            .into_iter()
-                            .map(|pt| ParamKind::from_string(pt.as_str()))
-                            .collect_vec(),
-                        name: parsed_function.name,
-                    }
-                })
-                .collect(),
+                .filter_map(|values| {
+                    Some(Call {
+                        calldata: encode_dispatched(&self.functions, function, &values)?.into(),
+                        ..call.clone()
+                    })
+                }),
+        );
+        candidates.dedup();
+        candidates
+    }
+}
+
+/// Generator of calls to several target contracts, each with its own
+/// [`SolidityFuzzer`] built from its ABI.
+pub struct MultiTargetFuzzer {
+    /// Name of each target contract along with the fuzzer of its functions.
+    fuzzers: Vec<(String, SolidityFuzzer)>,
+    /// Source of randomness for picking the target contract of each call.
+    rng: StdRng,
+}
+
+impl MultiTargetFuzzer {
+    /// Create a generator sending each call to a random one of `fuzzers`,
+    /// named after their target contract, picked with an RNG seeded with
+    /// `seed`.
+    ///
+    /// # Panics
+    /// Panics if `fuzzers` is empty.
+    pub fn new(fuzzers: Vec<(String, SolidityFuzzer)>, seed: u64) -> Self {
+        assert!(!fuzzers.is_empty(), "No target functions to call");
+        Self {
+            fuzzers,
+            rng: StdRng::seed_from_u64(seed),
         }
     }
 
-    pub fn generate_random_calldata(&self) -> Vec<u8> {
-        let mut rng = rand::thread_rng();
-        let mut calldata = vec![];
-        let function = &self.functions[rng.gen_range(0..self.functions.len())];
-        calldata.extend_from_slice(&function.selector);
-        function.params.iter().for_each(|p| {
-            calldata.extend_from_slice(&p.random());
-        });
-        println!("Call function {} with input {:?}", function.name, calldata);
-        calldata
+    /// Send calls to the target contract `name` to a random one of
+    /// `targets`. Ignored if there is no fuzzer for `name`, e.g. because
+    /// none of its functions are called.
+    pub fn set_targets(&mut self, name: &str, targets: Vec<Address>) {
+        if let Some((_, fuzzer)) = self.fuzzers.iter_mut().find(|(other, _)| other == name) {
+            fuzzer.set_targets(targets);
+        }
+    }
+
+    /// Draw address arguments from `addresses` some of the time. See
+    /// [`SolidityFuzzer::set_known_addresses`].
+    pub fn set_known_addresses(&mut self, addresses: Vec<Address>) {
+        for (_, fuzzer) in &mut self.fuzzers {
+            fuzzer.set_known_addresses(addresses.clone());
+        }
+    }
+
+    /// Generate a random call to a random target contract. See
+    /// [`SolidityFuzzer::generate_random_call`].
+    pub fn generate_random_call(&mut self) -> Call {
+        let idx = self.rng.gen_range(0..self.fuzzers.len());
+        self.fuzzers[idx].1.generate_random_call()
+    }
+
+    /// Generate ABI-encoded random arguments for parameters `params` with the
+    /// fuzzer of the first target contract. See
+    /// [`SolidityFuzzer::generate_random_args`].
+    pub fn generate_random_args(&mut self, params: &[ParsedParam]) -> Result<Vec<u8>, String> {
+        self.fuzzers[0].1.generate_random_args(params)
+    }
+
+    /// Return the name of the contract at `target` along with its fuzzer, if
+    /// it is one of the targets.
+    pub fn fuzzer(&self, target: Address) -> Option<(&str, &SolidityFuzzer)> {
+        self.fuzzers
+            .iter()
+            .find(|(_, fuzzer)| fuzzer.targets().contains(&target))
+            .map(|(name, fuzzer)| (name.as_str(), fuzzer))
+    }
+
+    /// Return the target function `call` calls, if any.
+    pub fn function(&self, call: &Call) -> Option<&FunctionSpec> {
+        self.fuzzer(call.target)?.1.function(&call.calldata)
+    }
+
+    /// Decode `call` as a call to one of the target functions and format it
+    /// for humans. See [`FunctionSpec::decode_call`].
+    pub fn decode_call(&self, call: &Call) -> Option<String> {
+        self.fuzzer(call.target)?.1.decode_call(&call.calldata)
+    }
+
+    /// Return the signature of the target function `call` calls, prefixed
+    /// with the contract name if there are several target contracts, e.g.
+    /// `Token.transfer(address,uint256)`.
+    pub fn function_label(&self, call: &Call) -> Option<String> {
+        let (name, fuzzer) = self.fuzzer(call.target)?;
+        let signature = fuzzer.function(&call.calldata)?.signature();
+        Some(match self.fuzzers.len() {
+            1 => signature.to_owned(),
+            _ => format!("{name}.{signature}"),
+        })
+    }
+
+    /// Add `calldata`, a call to one of the functions of the contract at
+    /// `target`, to the corpus of its fuzzer.
+    ///
+    /// # Panics
+    /// Panics if `target` isn't one of the targets, or if `calldata` is not a
+    /// valid call to one of its functions.
+    pub fn add_to_corpus(&mut self, target: Address, calldata: Vec<u8>) {
+        let (_, fuzzer) = self
+            .fuzzers
+            .iter_mut()
+            .find(|(_, fuzzer)| fuzzer.targets().contains(&target))
+            .expect("Corpus entry is not a call to a target");
+        fuzzer.add_to_corpus(calldata);
+    }
+
+    /// Return the number of entries in the corpora of all fuzzers.
+    pub fn corpus_len(&self) -> usize {
+        self.fuzzers
+            .iter()
+            .map(|(_, fuzzer)| fuzzer.corpus_len())
+            .sum()
+    }
+
+    /// Shrink `calls` with the fuzzer of the target of each call. See
+    /// [`SolidityFuzzer::shrink`].
+    pub fn shrink(
+        &self,
+        calls: Vec<Call>,
+        breaks_invariant: impl FnMut(&[Call]) -> bool,
+    ) -> Vec<Call> {
+        shrink_calls(
+            calls,
+            |call| {
+                self.fuzzer(call.target)
+                    .map(|(_, fuzzer)| fuzzer.shrink_candidates(call))
+                    .unwrap_or_default()
+            },
+            breaks_invariant,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse the ABI type `ty` of a parameter.
+    fn kind(ty: &str) -> ParamKind {
+        let param: ParsedParam =
+            serde_json::from_value(serde_json::json!({ "type": ty, "internalType": ty })).unwrap();
+        ParamKind::from_param(&param).unwrap()
+    }
+
+    /// Return the word of the two's-complement `value` sign-extended to 256
+    /// bits.
+    fn int_word(value: i128) -> [u8; 32] {
+        let fill = if value < 0 { 0xff } else { 0 };
+        let mut word = [fill; 32];
+        word[16..].copy_from_slice(&value.to_be_bytes());
+        word
+    }
+
+    #[test]
+    fn int_words_are_sign_extended() {
+        assert_eq!(int_word(-1), [0xff; 32]);
+        let mut min = [0xff; 32];
+        min[16..].copy_from_slice(&[0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(int_word(i128::MIN), min);
+        assert_eq!(int_word(0), [0; 32]);
+
+        let boundaries = int_boundaries(128);
+        for value in [-1, i128::MIN, 0, i128::MAX] {
+            assert!(boundaries.contains(&int_word(value)), "{value} is missing");
+            assert_eq!(
+                ParamValue::Word(int_word(value)).format(&ParamKind::Int(128)),
+                value.to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn int_boundaries_are_encoded_sign_extended() {
+        // A word with every byte but the last one set to `fill`.
+        let word = |fill: u8, last: u8| {
+            let mut word = [fill; 32];
+            word[31] = last;
+            word.to_vec()
+        };
+        let encoded = int_boundaries(8)
+            .into_iter()
+            .map(|word| encode_sequence(&[ParamValue::Word(word)]))
+            .collect_vec();
+        assert_eq!(
+            encoded,
+            [
+                word(0, 0),
+                word(0, 1),
+                word(0xff, 0xff), // -1
+                word(0, 0x7f),
+                word(0, 0x7e),
+                word(0xff, 0x80), // -128
+                word(0xff, 0x81),
+                word(0, 2),
+                word(0xff, 0xfe),
+                word(0, 10),
+                word(0xff, 0xf6),
+                word(0, 100),
+                word(0xff, 0x9c),
+            ]
+        );
+
+        let mut min = [0; 32];
+        min[0] = 0x80;
+        assert!(int_boundaries(256).contains(&min));
+        let value = ParamValue::Word(min);
+        assert_eq!(encode_sequence(&[value.clone()]), min);
+        assert_eq!(
+            value.format(&ParamKind::Int(256)),
+            "-57896044618658097711785492504343953926634992332820282019728792003956564819968"
+        );
+    }
+
+    #[test]
+    fn random_bools_are_zero_or_one() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut seen = [false; 2];
+        for _ in 0..1000 {
+            let ParamValue::Word(word) = kind("bool").random(&mut rng, &Dictionary::default())
+            else {
+                panic!("Expected a word");
+            };
+            assert!(word[..31].iter().all(|&byte| byte == 0));
+            assert!(word[31] <= 1);
+            seen[word[31] as usize] = true;
+        }
+        assert_eq!(seen, [true; 2]);
+    }
+
+    #[test]
+    fn random_ints_are_sign_extended() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for size in [8, 128, 256] {
+            let kind = kind(&format!("int{size}"));
+            for _ in 0..1000 {
+                let ParamValue::Word(word) = kind.random(&mut rng, &Dictionary::default()) else {
+                    panic!("Expected a word");
+                };
+                let fill = if word[32 - size / 8] & 0x80 != 0 {
+                    0xff
+                } else {
+                    0
+                };
+                assert!(word[..32 - size / 8].iter().all(|&byte| byte == fill));
+            }
+        }
+    }
+
+    #[test]
+    fn uints_of_any_width_are_left_padded() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for size in [24, 40, 72, 96] {
+            let kind = kind(&format!("uint{size}"));
+            assert_eq!(kind.type_name(), format!("uint{size}"));
+            let mut used = [0u8; 32];
+            for _ in 0..1000 {
+                let ParamValue::Word(word) = kind.random(&mut rng, &Dictionary::default()) else {
+                    panic!("Expected a word");
+                };
+                assert!(word[..32 - size / 8].iter().all(|&byte| byte == 0));
+                used.iter_mut()
+                    .zip(word)
+                    .for_each(|(used, byte)| *used |= byte);
+            }
+            // All the low bytes are random.
+            assert!(used[32 - size / 8..].iter().all(|&byte| byte == 0xff));
+        }
+    }
+
+    #[test]
+    fn invalid_widths_are_rejected() {
+        for ty in ["uint7", "uint300", "uint0", "int12", "uint"] {
+            let param: ParsedParam =
+                serde_json::from_value(serde_json::json!({ "type": ty, "internalType": ty }))
+                    .unwrap();
+            let err = ParamKind::from_param(&param).unwrap_err();
+            assert!(err.contains(ty), "{err}");
+        }
+    }
+
+    #[test]
+    fn fixed_bytes_are_right_padded() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let kind = kind("bytes4");
+        let mut used = [0u8; 32];
+        for _ in 0..1000 {
+            let ParamValue::Word(word) = kind.random(&mut rng, &Dictionary::default()) else {
+                panic!("Expected a word");
+            };
+            assert!(word[4..].iter().all(|&byte| byte == 0));
+            used.iter_mut()
+                .zip(word)
+                .for_each(|(used, byte)| *used |= byte);
+        }
+        assert_eq!(used[..4], [0xff; 4]);
+    }
+
+    #[test]
+    fn enums_stay_mostly_within_their_variants() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let dictionary = Dictionary::default();
+        let kind = ParamKind::Enum(3);
+        let in_range = |value: &ParamValue| {
+            let ParamValue::Word(word) = value else {
+                panic!("Expected a word");
+            };
+            assert!(word[..31].iter().all(|&byte| byte == 0));
+            word[31] < 3
+        };
+        // Out of range with probability INVALID_ENUM_PROBABILITY, that is 500
+        // times in 10000 on average.
+        let values = (0..10_000)
+            .map(|_| kind.random(&mut rng, &dictionary))
+            .collect_vec();
+        let invalid = values.iter().filter(|value| !in_range(value)).count();
+        assert!((400..600).contains(&invalid), "{invalid} invalid values");
+        for value in values.iter().filter(|value| in_range(value)) {
+            assert!(value.shrink_candidates(&kind).iter().all(in_range));
+        }
+        let invalid = values
+            .iter()
+            .map(|value| value.mutate(&kind, &mut rng, &dictionary))
+            .filter(|value| !in_range(value))
+            .count();
+        assert!((400..600).contains(&invalid), "{invalid} invalid mutations");
+    }
+
+    /// Return the words `values` as a single byte string.
+    fn words(values: &[usize]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|&value| encode_word(value))
+            .collect()
+    }
+
+    #[test]
+    fn static_fixed_arrays_are_inline() {
+        let kind = kind("uint256[3]");
+        assert!(!kind.is_dynamic());
+        assert_eq!(kind.head_len(), 96);
+        let value = ParamValue::Tuple((1..=3).map(|n| ParamValue::Word(encode_word(n))).collect());
+        let values = [value, ParamValue::Word(encode_word(4))];
+        let encoded = encode_sequence(&values);
+        assert_eq!(encoded, words(&[1, 2, 3, 4]));
+        assert_eq!(
+            decode_sequence([&kind, &ParamKind::Uint(256)], &encoded).unwrap(),
+            values
+        );
+    }
+
+    #[test]
+    fn dynamic_fixed_arrays_are_in_the_tail() {
+        let kind = kind("bytes[3]");
+        assert!(kind.is_dynamic());
+        let value = ParamValue::Tuple(vec![
+            ParamValue::Bytes(vec![0xaa]),
+            ParamValue::Bytes(vec![]),
+            ParamValue::Bytes(vec![0xbb; 33]),
+        ]);
+        let values = [value, ParamValue::Word(encode_word(4))];
+        let encoded = encode_sequence(&values);
+        // Head: offset of the array, then the integer.
+        let mut expected = words(&[0x40, 4]);
+        // Offsets of the elements from the start of the array.
+        expected.extend(words(&[0x60, 0xa0, 0xc0]));
+        // Elements: length and padded contents, 0xaa, empty, then 33 bytes.
+        expected.extend(words(&[1]));
+        expected.extend([0xaa].iter().chain(&[0; 31]));
+        expected.extend(words(&[0, 33]));
+        expected.extend([0xbb; 33].iter().chain(&[0; 31]));
+        assert_eq!(encoded, expected);
+        assert_eq!(
+            decode_sequence([&kind, &ParamKind::Uint(256)], &encoded).unwrap(),
+            values
+        );
+    }
+
+    #[test]
+    fn nested_static_arrays_are_inline() {
+        let kind = kind("uint256[3][2]");
+        assert_eq!(kind.type_name(), "uint256[3][2]");
+        assert!(!kind.is_dynamic());
+        // Two rows of three words.
+        assert_eq!(kind.head_len(), 192);
+        let row = |start: usize| {
+            ParamValue::Tuple(
+                (start..start + 3)
+                    .map(|n| ParamValue::Word(encode_word(n)))
+                    .collect(),
+            )
+        };
+        let values = [ParamValue::Tuple(vec![row(1), row(4)])];
+        let encoded = encode_sequence(&values);
+        assert_eq!(encoded, words(&[1, 2, 3, 4, 5, 6]));
+        assert_eq!(decode_sequence([&kind], &encoded).unwrap(), values);
+    }
+
+    #[test]
+    fn fixed_arrays_of_dynamic_arrays_are_in_the_tail() {
+        let kind = kind("uint256[][2]");
+        assert!(kind.is_dynamic());
+        let array = |elements: &[usize]| {
+            ParamValue::Array(
+                elements
+                    .iter()
+                    .map(|&n| ParamValue::Word(encode_word(n)))
+                    .collect(),
+            )
+        };
+        let values = [ParamValue::Tuple(vec![array(&[1, 2]), array(&[3])])];
+        let encoded = encode_sequence(&values);
+        let expected = words(&[
+            0x20, // Offset of the fixed array.
+            0x40, 0xa0, // Offsets of the dynamic arrays from its start.
+            2, 1, 2, // Length and elements of the first array.
+            1, 3, // Length and elements of the second array.
+        ]);
+        assert_eq!(encoded, expected);
+        assert_eq!(decode_sequence([&kind], &encoded).unwrap(), values);
+    }
+
+    #[test]
+    fn tuples_are_parsed_from_components() {
+        let param: ParsedParam = serde_json::from_value(serde_json::json!({
+            "type": "tuple[]",
+            "internalType": "struct S[]",
+            "components": [
+                { "type": "uint8", "internalType": "uint8" },
+                { "type": "address", "internalType": "address" },
+            ],
+        }))
+        .unwrap();
+        let ParamKind::Array(kind) = ParamKind::from_param(&param).unwrap() else {
+            panic!("Expected an array");
+        };
+        // A tuple of static members is encoded inline.
+        assert!(!kind.is_dynamic());
+        assert_eq!(kind.head_len(), 64);
+    }
+
+    #[test]
+    fn huge_lengths_are_invalid() {
+        let mut data = encode_word(0x20).to_vec();
+        data.extend([0xff; 32]);
+        assert_eq!(decode_sequence([&ParamKind::Bytes], &data), None);
+        data[32..56].fill(0);
+        assert_eq!(decode_sequence([&ParamKind::Bytes], &data), None);
+    }
+
+    #[test]
+    fn contract_params_are_hashed_as_addresses() {
+        let abi: Vec<ParsedFunction> = serde_json::from_value(serde_json::json!([{
+            "type": "function",
+            "name": "deposit",
+            "inputs": [
+                { "type": "address", "internalType": "contract IERC20" },
+                { "type": "uint256", "internalType": "uint256" },
+            ],
+            "stateMutability": "nonpayable",
+        }]))
+        .unwrap();
+        let fuzzer = SolidityFuzzer::new(abi, 0, vec![Address::ZERO]);
+        let function = &fuzzer.functions()[0];
+        assert_eq!(function.signature(), "deposit(address,uint256)");
+        assert_eq!(
+            function.selector(),
+            function_selector("deposit(address,uint256)")
+        );
+    }
+
+    /// Return an ABI with a single state-changing function `name` taking
+    /// parameters of the ABI types `types`.
+    fn abi(name: &str, types: &[&str]) -> Vec<ParsedFunction> {
+        let inputs = types
+            .iter()
+            .map(|ty| serde_json::json!({ "type": ty, "internalType": ty }))
+            .collect_vec();
+        serde_json::from_value(serde_json::json!([{
+            "type": "function",
+            "name": name,
+            "inputs": inputs,
+            "stateMutability": "nonpayable",
+        }]))
+        .unwrap()
+    }
+
+    #[test]
+    fn calldata_decodes_with_ethabi() {
+        let types = [
+            "uint256", "bytes", "string", "uint8[]", "bool", "address", "int64", "bytes4",
+            "bytes[2]", "string[]",
+        ];
+        let mut fuzzer = SolidityFuzzer::new(abi("f", &types), 0, vec![Address::ZERO]);
+        let param_types = types
+            .iter()
+            .map(|ty| ethabi::param_type::Reader::read(ty).unwrap())
+            .collect_vec();
+        let selector = ethabi::short_signature("f", &param_types);
+        for _ in 0..1000 {
+            let (_, calldata) = fuzzer.generate_random_calldata();
+            assert_eq!(calldata[..4], selector);
+            let tokens = ethabi::decode(&param_types, &calldata[4..]).unwrap();
+            // Decoding is lenient, so check that the layout is the canonical one.
+            assert_eq!(ethabi::encode(&tokens), calldata[4..]);
+        }
+    }
+
+    #[test]
+    fn tuple_calldata_decodes_with_ethabi() {
+        let tuple = |ty: &str, components: &[&str]| {
+            let components = components
+                .iter()
+                .map(|ty| serde_json::json!({ "type": ty, "internalType": ty }))
+                .collect_vec();
+            serde_json::json!({ "type": ty, "internalType": "struct S", "components": components })
+        };
+        let abi: Vec<ParsedFunction> = serde_json::from_value(serde_json::json!([{
+            "type": "function",
+            "name": "f",
+            "inputs": [
+                tuple("tuple", &["uint256", "bytes"]),
+                tuple("tuple", &["uint8", "address"]),
+                tuple("tuple[]", &["uint256", "bytes"]),
+                tuple("tuple[2]", &["uint8", "address"]),
+            ],
+            "stateMutability": "nonpayable",
+        }]))
+        .unwrap();
+        let mut fuzzer = SolidityFuzzer::new(abi, 0, vec![Address::ZERO]);
+        let param_types = [
+            "(uint256,bytes)",
+            "(uint8,address)",
+            "(uint256,bytes)[]",
+            "(uint8,address)[2]",
+        ]
+        .iter()
+        .map(|ty| ethabi::param_type::Reader::read(ty).unwrap())
+        .collect_vec();
+        let selector = ethabi::short_signature("f", &param_types);
+        for _ in 0..1000 {
+            let (_, calldata) = fuzzer.generate_random_calldata();
+            assert_eq!(calldata[..4], selector);
+            let tokens = ethabi::decode(&param_types, &calldata[4..]).unwrap();
+            assert_eq!(ethabi::encode(&tokens), calldata[4..]);
+        }
+    }
+
+    #[test]
+    fn overloads_are_distinct_functions() {
+        let mut functions = abi("foo", &["uint256"]);
+        functions.extend(abi("foo", &["address"]));
+        let mut fuzzer = SolidityFuzzer::new(functions, 0, vec![Address::ZERO]);
+        let signatures = fuzzer
+            .functions()
+            .iter()
+            .map(FunctionSpec::signature)
+            .collect_vec();
+        assert_eq!(signatures, ["foo(uint256)", "foo(address)"]);
+        assert_ne!(
+            fuzzer.functions()[0].selector(),
+            fuzzer.functions()[1].selector()
+        );
+
+        // Both overloads are called.
+        let called = (0..100)
+            .map(|_| fuzzer.generate_random_calldata().0.signature().to_owned())
+            .unique()
+            .count();
+        assert_eq!(called, 2);
+    }
+
+    #[test]
+    fn calls_are_decoded_for_humans() {
+        let fuzzer = SolidityFuzzer::new(
+            abi("f", &["uint8", "int16", "bool", "string"]),
+            0,
+            vec![Address::ZERO],
+        );
+        let mut calldata = function_selector("f(uint8,int16,bool,string)").to_vec();
+        calldata.extend(ethabi::encode(&[
+            ethabi::Token::Uint(42.into()),
+            // -2 in two's complement.
+            ethabi::Token::Int(ethabi::Int::MAX - 1),
+            ethabi::Token::Bool(true),
+            ethabi::Token::String("hi".to_owned()),
+        ]));
+        assert_eq!(
+            fuzzer.decode_call(&calldata).unwrap(),
+            "f(uint8=42, int16=-2, bool=true, string=\"hi\")"
+        );
+        assert_eq!(fuzzer.decode_call(&calldata[..4]), None);
+    }
+
+    #[test]
+    fn args_are_written_as_solidity_literals() {
+        let types = [
+            "uint8", "int16", "address", "bool", "bytes2", "bytes", "string", "uint8[2]",
+        ];
+        let fuzzer = SolidityFuzzer::new(abi("f", &types), 0, vec![Address::ZERO]);
+        let function = &fuzzer.functions()[0];
+        let address = "0x52908400098527886E0F7030069857D2E4169EE7";
+        let mut calldata = function.selector().to_vec();
+        calldata.extend(ethabi::encode(&[
+            ethabi::Token::Uint(42.into()),
+            // -2 in two's complement.
+            ethabi::Token::Int(ethabi::Int::MAX - 1),
+            ethabi::Token::Address(address.parse().unwrap()),
+            ethabi::Token::Bool(true),
+            ethabi::Token::FixedBytes(vec![0xab, 0xcd]),
+            ethabi::Token::Bytes(vec![1, 2]),
+            ethabi::Token::String("hi".to_owned()),
+            ethabi::Token::FixedArray(vec![
+                ethabi::Token::Uint(1.into()),
+                ethabi::Token::Uint(2.into()),
+            ]),
+        ]));
+        assert_eq!(
+            function.solidity_args(&calldata).unwrap(),
+            [
+                "uint8(42)",
+                "int16(-2)",
+                address,
+                "true",
+                "bytes2(0xabcd)",
+                "bytes(hex\"0102\")",
+                "string(hex\"6869\")",
+                "[uint8(1), uint8(2)]",
+            ]
+        );
+
+        // Dynamic arrays have no literals.
+        let fuzzer = SolidityFuzzer::new(abi("g", &["uint256[]"]), 0, vec![Address::ZERO]);
+        let function = &fuzzer.functions()[0];
+        let mut calldata = function.selector().to_vec();
+        calldata.extend(ethabi::encode(&[ethabi::Token::Array(vec![])]));
+        assert_eq!(function.solidity_args(&calldata), None);
+    }
+
+    #[test]
+    fn view_and_pure_functions_are_skipped() {
+        let abi: Vec<ParsedFunction> = serde_json::from_value(serde_json::json!([
+            { "type": "function", "name": "get", "stateMutability": "view" },
+            { "type": "function", "name": "hash", "stateMutability": "pure" },
+            { "type": "function", "name": "set", "stateMutability": "nonpayable" },
+        ]))
+        .unwrap();
+        let mut fuzzer = SolidityFuzzer::new(abi, 0, vec![Address::ZERO]);
+        for _ in 0..100 {
+            assert_eq!(fuzzer.generate_random_calldata().0.name(), "set");
+        }
+    }
+
+    #[test]
+    fn fallback_and_receive_get_their_own_calldata() {
+        let abi: Vec<ParsedFunction> = serde_json::from_value(serde_json::json!([
+            {
+                "type": "function",
+                "name": "set",
+                "inputs": [{ "type": "uint256", "internalType": "uint256" }],
+                "stateMutability": "nonpayable",
+            },
+            { "type": "fallback", "stateMutability": "nonpayable" },
+            { "type": "receive", "stateMutability": "payable" },
+        ]))
+        .unwrap();
+        let mut fuzzer = SolidityFuzzer::new(abi, 0, vec![Address::ZERO]);
+        fuzzer.set_targets(vec![Address::with_last_byte(1)]);
+        let selector = function_selector("set(uint256)");
+        let mut called = vec![];
+        for _ in 0..1000 {
+            let call = fuzzer.generate_random_call();
+            let function = fuzzer.function(&call.calldata).unwrap();
+            match function.kind {
+                FunctionKind::Fallback => {
+                    assert!(!call.calldata.is_empty());
+                    assert!(!call.calldata.starts_with(&selector));
+                    assert_eq!(call.value, U256::ZERO);
+                }
+                FunctionKind::Receive => {
+                    assert!(call.calldata.is_empty());
+                    assert_ne!(call.value, U256::ZERO);
+                }
+                FunctionKind::Function => assert!(call.calldata.starts_with(&selector)),
+            }
+            called.push(function.signature().to_owned());
+        }
+        assert_eq!(called.iter().unique().count(), 3);
+    }
+
+    #[test]
+    fn functions_with_unsupported_params_are_skipped() {
+        let abi: Vec<ParsedFunction> = serde_json::from_value(serde_json::json!([
+            {
+                "type": "function",
+                "name": "callback",
+                "inputs": [{ "type": "function", "internalType": "function () external" }],
+                "stateMutability": "nonpayable",
+            },
+            {
+                "type": "function",
+                "name": "set",
+                "inputs": [{ "type": "uint24", "internalType": "uint24" }],
+                "stateMutability": "nonpayable",
+            },
+        ]))
+        .unwrap();
+        let fuzzer = SolidityFuzzer::new(abi, 0, vec![Address::ZERO]);
+        let signatures = fuzzer
+            .functions()
+            .iter()
+            .map(FunctionSpec::signature)
+            .collect_vec();
+        assert_eq!(signatures, ["set(uint24)"]);
     }
 }
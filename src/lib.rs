@@ -0,0 +1,23 @@
+//! Invariant fuzzer for Solidity contracts.
+//!
+//! [`compile_solidity`] compiles target contracts and an invariant checker
+//! deploying them, and [`Fuzzer`] runs a fuzzing campaign against them.
+
+pub mod campaign;
+pub mod corpus;
+pub mod coverage;
+pub mod evm;
+pub mod fuzzer;
+pub mod solc;
+#[doc(hidden)]
+pub mod test_support;
+
+pub use campaign::{
+    Crash, FoundCrash, FuzzConfig, FuzzResult, Fuzzer, GasStats, Invariant, SetupError,
+};
+pub use evm::Evm;
+pub use fuzzer::{MultiTargetFuzzer, SolidityFuzzer};
+pub use solc::{
+    compile_solidity, parse_solc_output, CompilationOutput, CompileError, ParsedFunction,
+    ParsedParam,
+};
@@ -1,160 +1,992 @@
-use evm::Evm;
-use fuzzer::{function_selector, SolidityFuzzer};
-use revm::primitives::Address;
+use clap::{
+    builder::PossibleValuesParser, parser::ValueSource, ArgAction, ArgMatches, CommandFactory,
+    FromArgMatches, Parser,
+};
+use glob::Pattern;
+use log::{error, info, warn, LevelFilter};
+use revm::primitives::{Address, U256};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::panic::{self, AssertUnwindSafe};
-use std::process::{Command, Stdio};
-use std::{io, str};
+use simple_fuzzer::campaign::{Crash, FuzzConfig, FuzzResult, Fuzzer, CALLER_BALANCE};
+use simple_fuzzer::evm::{spec_id, DEFAULT_CALLER, EVM_VERSIONS};
+use simple_fuzzer::fuzzer::{Call, MultiTargetFuzzer};
+use simple_fuzzer::solc::compile_solidity;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{env, fs, path::PathBuf};
 
-pub mod evm;
-pub mod fuzzer;
+/// Format the sequence of `calls` decoded as calls to the target functions,
+/// one line per detail.
+fn format_calls(target_fuzzer: &MultiTargetFuzzer, calls: &[Call]) -> String {
+    let mut lines = vec![];
+    for (idx, call) in calls.iter().enumerate() {
+        lines.push(format!(
+            "  {}. {}",
+            idx + 1,
+            target_fuzzer.decode_call(call).unwrap()
+        ));
+        lines.push(format!("     sender: {}", call.sender));
+        let (contract, _) = target_fuzzer.fuzzer(call.target).unwrap();
+        lines.push(format!("     target: {} ({contract})", call.target));
+        lines.push(format!("     calldata: 0x{}", hex::encode(&call.calldata)));
+        if call.value != U256::ZERO {
+            lines.push(format!("     value: {}", call.value));
+        }
+        if call.time_delay != 0 {
+            lines.push(format!("     time delay: {}s", call.time_delay));
+        }
+    }
+    lines.join("\n")
+}
 
-/// --- Solidity Compilation Helpers ---
+/// --- Crash Files ---
 
-/// The JSON structure output by solc with --combined-json bin,abi.
-#[derive(Deserialize)]
-struct ParsedResult {
-    contracts: HashMap<String, ParsedContract>,
+/// Directory crashing inputs are saved to.
+const CRASHES_DIR: &str = "crashes";
+
+/// A crashing input as saved to and loaded from a JSON crash file.
+#[derive(Serialize, Deserialize)]
+struct CrashFile {
+    /// Seed of the run, which also determines the constructor arguments.
+    seed: u64,
+    /// EVM version the contracts were compiled for and executed with, if
+    /// given. Replays use it unless another one is given.
+    #[serde(default)]
+    evm_version: Option<String>,
+    /// Description of the crash, e.g. the broken invariant.
+    crash: String,
+    calls: Vec<CrashCall>,
 }
 
-#[derive(Deserialize)]
-struct ParsedContract {
-    abi: Vec<ParsedFunction>,
-    bin: String,
+/// A call of a [`CrashFile`]. Only `sender`, `target`, `calldata`, `value`
+/// and `time_delay` are used on replay, the rest is for humans.
+#[derive(Serialize, Deserialize)]
+struct CrashCall {
+    /// Name of the target contract.
+    contract: String,
+    function: String,
+    args: Vec<String>,
+    sender: String,
+    target: String,
+    /// Hex-encoded calldata, with `0x` prefix.
+    calldata: String,
+    /// Value in wei, in decimal.
+    value: String,
+    time_delay: u64,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct ParsedFunction {
-    name: String,
-    inputs: Vec<ParsedParam>,
+impl CrashFile {
+    fn new(
+        target_fuzzer: &MultiTargetFuzzer,
+        seed: u64,
+        evm_version: Option<&str>,
+        crash: &Crash,
+        calls: &[Call],
+    ) -> Self {
+        let calls = calls
+            .iter()
+            .map(|call| {
+                let (contract, solidity_fuzzer) = target_fuzzer.fuzzer(call.target).unwrap();
+                let function = solidity_fuzzer.function(&call.calldata).unwrap();
+                CrashCall {
+                    contract: contract.to_owned(),
+                    function: function.name().to_owned(),
+                    args: function.decode_args(&call.calldata).unwrap(),
+                    sender: call.sender.to_string(),
+                    target: call.target.to_string(),
+                    calldata: format!("0x{}", hex::encode(&call.calldata)),
+                    value: call.value.to_string(),
+                    time_delay: call.time_delay,
+                }
+            })
+            .collect();
+        CrashFile {
+            seed,
+            evm_version: evm_version.map(str::to_owned),
+            crash: crash.to_string(),
+            calls,
+        }
+    }
+
+    /// Save to a new file named after the current time in [`CRASHES_DIR`] and
+    /// return its path.
+    fn save(&self) -> PathBuf {
+        fs::create_dir_all(CRASHES_DIR).unwrap();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let path = PathBuf::from(CRASHES_DIR).join(format!("{timestamp}.json"));
+        fs::write(&path, serde_json::to_string_pretty(self).unwrap()).unwrap();
+        path
+    }
+
+    fn load(path: &str) -> Self {
+        let contents = fs::read_to_string(path).unwrap();
+        serde_json::from_str(&contents).unwrap()
+    }
+
+    /// The calls to replay.
+    fn calls(&self) -> Vec<Call> {
+        self.calls
+            .iter()
+            .map(|call| Call {
+                calldata: hex::decode(call.calldata.strip_prefix("0x").unwrap_or(&call.calldata))
+                    .unwrap()
+                    .into(),
+                value: call.value.parse().unwrap(),
+                sender: parse_address(&call.sender).unwrap(),
+                target: parse_address(&call.target).unwrap(),
+                time_delay: call.time_delay,
+            })
+            .collect()
+    }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-struct ParsedParam {
-    #[serde(rename = "internalType")]
-    internal_type: String,
+/// --- Foundry Tests ---
+
+/// Return a Foundry test reproducing `crash` by deploying the invariant
+/// checker named `invariant_checker` from `sources` like `fuzzer` did, then
+/// applying `calls` and checking that the crash happens again. Returns `None`
+/// if `calls` don't cause `crash` right after `setUp()`, so the test would
+/// pass.
+///
+/// Calldata is encoded with `abi.encodeWithSignature` and typed arguments if
+/// it decodes as a call to a target function, and given as raw bytes
+/// otherwise. Addresses passed as arguments only match if the contracts are
+/// deployed at the same addresses, which the test checks for the invariant
+/// checker.
+fn foundry_test(
+    fuzzer: &Fuzzer,
+    sources: &[String],
+    invariant_checker: &str,
+    crash: &Crash,
+    calls: &[Call],
+) -> Option<String> {
+    let config = fuzzer.config();
+    let target_fuzzer = fuzzer.target_fuzzer();
+    // Calls after a panicking one aren't applied.
+    let calls = match crash {
+        Crash::Panic(_) => (1..=calls.len())
+            .find(|&len| fuzzer.replay(&calls[..len]).as_ref() == Some(crash))
+            .map_or(calls, |len| &calls[..len]),
+        _ => calls,
+    };
+    if fuzzer.replay(calls).as_ref() != Some(crash) {
+        return None;
+    }
+    let address = |address: Address| address.to_checksum(None);
+    let contract = invariant_checker.rsplit(':').next().unwrap();
+
+    let mut test = String::new();
+    writeln!(test, "// SPDX-License-Identifier: UNLICENSED").unwrap();
+    writeln!(test, "pragma solidity >=0.8.0;\n").unwrap();
+    writeln!(test, "import \"forge-std/Test.sol\";").unwrap();
+    for source in sources {
+        writeln!(test, "import \"{source}\";").unwrap();
+    }
+    writeln!(test, "\n/// Reproduces: {crash}").unwrap();
+    writeln!(
+        test,
+        "/// Generated by simple_fuzzer with seed {}.",
+        config.seed
+    )
+    .unwrap();
+    writeln!(test, "contract ReproduceCrashTest is Test {{").unwrap();
+    writeln!(
+        test,
+        "    address constant DEPLOYER = {};",
+        address(DEFAULT_CALLER)
+    )
+    .unwrap();
+    writeln!(test, "    address checker;\n").unwrap();
+
+    writeln!(test, "    function setUp() public {{").unwrap();
+    writeln!(test, "        vm.warp({});", fuzzer.block_timestamp()).unwrap();
+    writeln!(
+        test,
+        "        bytes memory creationCode = type({contract}).creationCode;"
+    )
+    .unwrap();
+    if !fuzzer.constructor_args().is_empty() {
+        writeln!(
+            test,
+            "        creationCode = abi.encodePacked(creationCode, hex\"{}\");",
+            hex::encode(fuzzer.constructor_args())
+        )
+        .unwrap();
+    }
+    writeln!(test, "        vm.prank(DEPLOYER);").unwrap();
+    writeln!(test, "        address deployed;").unwrap();
+    writeln!(test, "        assembly {{").unwrap();
+    writeln!(
+        test,
+        "            deployed := create(0, add(creationCode, 0x20), mload(creationCode))"
+    )
+    .unwrap();
+    writeln!(test, "        }}").unwrap();
+    writeln!(
+        test,
+        "        require(deployed == {}, \"invariant checker deployed at another address\");",
+        address(fuzzer.invariant_checker_address())
+    )
+    .unwrap();
+    writeln!(test, "        checker = deployed;").unwrap();
+    writeln!(
+        test,
+        "        _checkerCall(\"{}()\");",
+        config.setup_function
+    )
+    .unwrap();
+    writeln!(
+        test,
+        "        _checkerCall(\"{}()\");",
+        config.targets_function
+    )
+    .unwrap();
+    for sender in &config.senders {
+        writeln!(
+            test,
+            "        vm.deal({}, {CALLER_BALANCE});",
+            address(*sender)
+        )
+        .unwrap();
+    }
+    writeln!(test, "    }}\n").unwrap();
+
+    writeln!(test, "    function test_reproduce() public {{").unwrap();
+    let mut timestamp = fuzzer.block_timestamp();
+    for (idx, call) in calls.iter().enumerate() {
+        writeln!(
+            test,
+            "        // {}. {}",
+            idx + 1,
+            target_fuzzer.decode_call(call).unwrap()
+        )
+        .unwrap();
+        if call.time_delay != 0 {
+            timestamp = timestamp.saturating_add(call.time_delay);
+            writeln!(test, "        vm.warp({timestamp});").unwrap();
+        }
+        let is_panicking = matches!(crash, Crash::Panic(_)) && idx == calls.len() - 1;
+        let assignment = if is_panicking {
+            "(bool success, bytes memory output) = "
+        } else {
+            ""
+        };
+        let calldata = target_fuzzer
+            .function(call)
+            .and_then(|function| {
+                let mut args = function.solidity_args(&call.calldata)?;
+                args.insert(0, format!("\"{}\"", function.signature()));
+                Some(format!("abi.encodeWithSignature({})", args.join(", ")))
+            })
+            .unwrap_or_else(|| format!("hex\"{}\"", hex::encode(&call.calldata)));
+        writeln!(
+            test,
+            "        {assignment}_call({}, {}, {}, {calldata});",
+            address(call.sender),
+            address(call.target),
+            call.value,
+        )
+        .unwrap();
+    }
+    match crash {
+        Crash::Panic(code) => {
+            writeln!(test, "        assertFalse(success);").unwrap();
+            writeln!(
+                test,
+                "        assertEq(output, abi.encodeWithSignature(\"Panic(uint256)\", {code}));"
+            )
+            .unwrap();
+        }
+        Crash::BrokenInvariant(signature)
+        | Crash::InvalidInvariantOutput(signature, _)
+        | Crash::RevertedInvariant(signature, _)
+        | Crash::HaltedInvariant(signature, _) => {
+            // Earlier invariants are checked first and may change the state.
+            for invariant in fuzzer.invariants() {
+                if invariant.signature == *signature {
+                    break;
+                }
+                writeln!(test, "        _checkerCall(\"{}\");", invariant.signature).unwrap();
+            }
+            match crash {
+                Crash::RevertedInvariant(..) | Crash::HaltedInvariant(..) => {
+                    // A halt has no output to compare.
+                    let results = match crash {
+                        Crash::RevertedInvariant(..) => "bool success, bytes memory output",
+                        _ => "bool success, ",
+                    };
+                    writeln!(test, "        vm.prank(DEPLOYER);").unwrap();
+                    writeln!(
+                        test,
+                        "        ({results}) = \
+                         checker.call(abi.encodeWithSignature(\"{signature}\"));"
+                    )
+                    .unwrap();
+                    writeln!(test, "        assertFalse(success);").unwrap();
+                    if let Crash::RevertedInvariant(_, output) = crash {
+                        writeln!(
+                            test,
+                            "        assertEq(output, hex\"{}\");",
+                            hex::encode(output)
+                        )
+                        .unwrap();
+                    }
+                }
+                _ => {
+                    let expected = match crash {
+                        Crash::InvalidInvariantOutput(_, output) => {
+                            format!("hex\"{}\"", hex::encode(output))
+                        }
+                        _ => "abi.encode(false)".to_owned(),
+                    };
+                    writeln!(
+                        test,
+                        "        assertEq(_checkerCall(\"{signature}\"), {expected});"
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    }
+    writeln!(test, "    }}\n").unwrap();
+
+    writeln!(
+        test,
+        "    function _call(address sender, address target, uint256 value, bytes memory data)"
+    )
+    .unwrap();
+    writeln!(test, "        internal").unwrap();
+    writeln!(test, "        returns (bool success, bytes memory output)").unwrap();
+    writeln!(test, "    {{").unwrap();
+    writeln!(test, "        vm.prank(sender);").unwrap();
+    writeln!(
+        test,
+        "        (success, output) = target.call{{value: value}}(data);"
+    )
+    .unwrap();
+    writeln!(test, "    }}\n").unwrap();
+
+    writeln!(test, "    function _checkerCall(string memory signature)").unwrap();
+    writeln!(test, "        internal").unwrap();
+    writeln!(test, "        returns (bytes memory output)").unwrap();
+    writeln!(test, "    {{").unwrap();
+    writeln!(test, "        bool success;").unwrap();
+    writeln!(test, "        vm.prank(DEPLOYER);").unwrap();
+    writeln!(
+        test,
+        "        (success, output) = checker.call(abi.encodeWithSignature(signature));"
+    )
+    .unwrap();
+    writeln!(test, "        require(success, signature);").unwrap();
+    writeln!(test, "    }}").unwrap();
+    writeln!(test, "}}").unwrap();
+    Some(test)
 }
 
-pub struct CompilationOutput {
-    invariant_checker: (Vec<u8>, Vec<ParsedFunction>),
-    target_abi: Vec<ParsedFunction>,
+/// --- Config Files ---
+
+/// Settings of a campaign loaded from a TOML file given with `--config`, so
+/// that it can be checked into a repository. Keys are named after the fields
+/// of [`Args`] in kebab case, e.g. `max-time-delay = 60` and
+/// `sources = ["a.sol", "b.sol"]`, and `weights` is a table of weights by
+/// function name.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct ConfigFile {
+    solc: Option<String>,
+    optimizer_runs: Option<u32>,
+    sources: Option<Vec<String>>,
+    remappings: Option<Vec<String>>,
+    targets: Option<Vec<String>>,
+    invariant: Option<String>,
+    setup_function: Option<String>,
+    targets_function: Option<String>,
+    seed: Option<u64>,
+    isolated: Option<bool>,
+    senders: Option<Vec<String>>,
+    depth: Option<usize>,
+    evm_version: Option<String>,
+    max_time_delay: Option<u64>,
+    panics: Option<bool>,
+    duration: Option<u64>,
+    max_iterations: Option<u64>,
+    dictionary: Option<bool>,
+    coverage: Option<bool>,
+    corpus_dir: Option<PathBuf>,
+    mutation_ratio: Option<f64>,
+    jobs: Option<u64>,
+    include: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+    weights: Option<BTreeMap<String, u32>>,
+    gas_report: Option<bool>,
+    emit_foundry: Option<PathBuf>,
+    stats_interval: Option<u64>,
 }
 
-/// Compiles Solidity source code (via solc) with optimization and returns both
-/// the creation bytecode and ABI. It reads the Solidity source from the provided input.
-pub fn compile_solidity(target_name: &str, invariant_checker_name: &str) -> CompilationOutput {
-    let process = match Command::new("solc")
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .arg("--combined-json")
-        .arg("bin,abi")
-        .arg("-")
-        .arg("contract/contract.sol")
-        .spawn()
-    {
-        Ok(process) => process,
-        Err(err) if err.kind() == io::ErrorKind::NotFound => {
-            panic!("Command 'solc' not found");
-        }
-        Err(err) => {
-            panic!("Failed to spwan process with command 'solc':\n{err}");
+impl ConfigFile {
+    /// # Panics
+    /// Panics if the file can't be read or isn't a valid config.
+    fn load(path: &PathBuf) -> Self {
+        let contents = fs::read_to_string(path)
+            .unwrap_or_else(|err| panic!("Failed to read config {}: {err}", path.display()));
+        toml::from_str(&contents)
+            .unwrap_or_else(|err| panic!("Invalid config {}: {err}", path.display()))
+    }
+
+    /// Override the settings of `args` that weren't given on the command line
+    /// `matches` with the ones set in this file.
+    ///
+    /// # Panics
+    /// Panics if a setting has an invalid value.
+    fn apply(self, args: &mut Args, matches: &ArgMatches) {
+        let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        macro_rules! apply {
+            ($($field:ident),*) => {$(
+                if let Some(value) = self.$field {
+                    if !given(stringify!($field)) {
+                        args.$field = value.into();
+                    }
+                }
+            )*};
         }
-    };
-    let output = process.wait_with_output().unwrap();
-    let stdout = str::from_utf8(&output.stdout).unwrap();
-    let parsed_result: ParsedResult = serde_json::from_str(stdout).unwrap_or_else(|err| {
-        panic!(
-            "Failed to parse solc JSON output: {err}\nOutput: {}",
-            stdout
-        )
-    });
-    let target_name = "contract/contract.sol:".to_string() + target_name;
-    let invariant_checker_name = "contract/contract.sol:".to_string() + invariant_checker_name;
-    parsed_result
-        .contracts
-        .get(target_name.as_str())
-        .map(|target| {
-            parsed_result
-                .contracts
-                .get(invariant_checker_name.as_str())
-                .map(|invariant_checker| CompilationOutput {
-                    target_abi: target.abi.to_vec(),
-                    invariant_checker: (
-                        hex::decode(invariant_checker.bin.as_str())
-                            .expect("Invalid hex in contract bytecode"),
-                        invariant_checker.abi.to_vec(),
-                    ),
-                })
-                .unwrap_or_else(|| {
-                    panic!("Invariant checker not found");
+        apply!(
+            solc,
+            optimizer_runs,
+            sources,
+            targets,
+            invariant,
+            setup_function,
+            targets_function,
+            seed,
+            isolated,
+            depth,
+            max_time_delay,
+            panics,
+            duration,
+            max_iterations,
+            dictionary,
+            coverage,
+            corpus_dir,
+            mutation_ratio,
+            jobs,
+            gas_report,
+            stats_interval,
+            emit_foundry
+        );
+        let invalid = |key: &str, err: String| -> ! { panic!("Invalid {key} in config: {err}") };
+        if let Some(remappings) = self.remappings.filter(|_| !given("remappings")) {
+            for remapping in &remappings {
+                parse_remapping(remapping).unwrap_or_else(|err| invalid("remappings", err));
+            }
+            args.remappings = remappings;
+        }
+        if let Some(senders) = self.senders.filter(|_| !given("senders")) {
+            args.senders = senders
+                .iter()
+                .map(|sender| parse_address(sender).unwrap_or_else(|err| invalid("senders", err)))
+                .collect();
+        }
+        if let Some(evm_version) = self.evm_version.filter(|_| !given("evm_version")) {
+            if spec_id(&evm_version).is_none() {
+                invalid(
+                    "evm-version",
+                    format!("unknown EVM version '{evm_version}'"),
+                );
+            }
+            args.evm_version = Some(evm_version);
+        }
+        let patterns = |key: &str, patterns: Vec<String>| {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    Pattern::new(pattern).unwrap_or_else(|err| invalid(key, err.to_string()))
                 })
-        })
-        .unwrap_or_else(|| {
-            panic!("Target not found");
-        })
+                .collect()
+        };
+        if let Some(include) = self.include.filter(|_| !given("include")) {
+            args.include = patterns("include", include);
+        }
+        if let Some(exclude) = self.exclude.filter(|_| !given("exclude")) {
+            args.exclude = patterns("exclude", exclude);
+        }
+        if let Some(weights) = self.weights.filter(|_| !given("weights")) {
+            args.weights = weights.into_iter().collect();
+        }
+        if !(0.0..=1.0).contains(&args.mutation_ratio) {
+            invalid("mutation-ratio", "must be between 0 and 1".to_owned());
+        }
+        if args.jobs == 0 {
+            invalid("jobs", "must be at least 1".to_owned());
+        }
+        if args.stats_interval == 0 {
+            invalid("stats-interval", "must be at least 1".to_owned());
+        }
+    }
 }
 
-pub fn deploy_invariant_checker(runner: &mut Evm, bytecode: Vec<u8>) -> Address {
-    runner.create(bytecode)
+/// Command line arguments.
+#[derive(Parser)]
+#[command(about = "Invariant fuzzer for Solidity contracts")]
+struct Args {
+    /// Load settings from a TOML file. Settings given on the command line
+    /// take precedence over the file, which takes precedence over the
+    /// defaults.
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Path to the solc binary. Defaults to `$SOLC` if set, or else `solc` on
+    /// `PATH`.
+    #[arg(long)]
+    solc: Option<String>,
+    /// Enable the solc optimizer, tuned for this many runs.
+    #[arg(long)]
+    optimizer_runs: Option<u32>,
+    /// Path to a Solidity source file. Can be given multiple times.
+    #[arg(long = "source", default_value = "contract/contract.sol")]
+    sources: Vec<String>,
+    /// Import remapping passed to solc, e.g.
+    /// `@openzeppelin/=lib/openzeppelin-contracts/`. Can be given multiple
+    /// times.
+    #[arg(long = "remap", value_name = "PREFIX=PATH", value_parser = parse_remapping)]
+    remappings: Vec<String>,
+    /// Name of the target contract, either `Name` or `file:Name` if the name
+    /// is ambiguous. Can be given multiple times if the targets function
+    /// returns one target per target contract, in the same order.
+    #[arg(long = "target", default_value = "InvariantBreaker")]
+    targets: Vec<String>,
+    /// Name of the invariant checker contract, either `Name` or `file:Name`
+    /// if the name is ambiguous.
+    #[arg(long, default_value = "InvariantTest")]
+    invariant: String,
+    /// Function of the invariant checker deploying the targets.
+    #[arg(long, default_value = "setUp")]
+    setup_function: String,
+    /// Function of the invariant checker returning the deployed targets, an
+    /// `address` or an `address[]`. Calls go to a random one of them.
+    #[arg(long, default_value = "inv")]
+    targets_function: String,
+    /// Seed for the random input generator. A random seed is used if omitted.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Run each call against the state right after `setUp()` instead of
+    /// accumulating state across up to 100 calls.
+    #[arg(long)]
+    isolated: bool,
+    /// Address to send calls from. Can be given multiple times to pick a
+    /// random sender for each call. Defaults to the zero address.
+    #[arg(long = "sender", value_parser = parse_address)]
+    senders: Vec<Address>,
+    /// Apply sequences of this many calls, each starting from the state right
+    /// after `setUp()`, and check the invariant at the end of each sequence.
+    #[arg(long)]
+    depth: Option<usize>,
+    /// EVM version to compile for and execute with, e.g. `shanghai`. Defaults
+    /// to solc's default and the latest hardfork supported by revm.
+    #[arg(long, value_parser = PossibleValuesParser::new(EVM_VERSIONS.map(|(name, _)| name)))]
+    evm_version: Option<String>,
+    /// Advance `block.timestamp` by a random number of seconds up to this
+    /// bound before each call.
+    #[arg(long, default_value_t = 0)]
+    max_time_delay: u64,
+    /// Report any call reverting with `Panic(uint256)`, e.g. a failing
+    /// `assert` or an arithmetic overflow, as a crash.
+    #[arg(long)]
+    panics: bool,
+    /// Stop after this many seconds and print a summary of the run.
+    #[arg(long)]
+    duration: Option<u64>,
+    /// Stop after this many iterations and print a summary of the run.
+    #[arg(long)]
+    max_iterations: Option<u64>,
+    /// Replay the calls of a crash file against a fresh deployment and check
+    /// that they still crash, instead of fuzzing. Calls are applied to the
+    /// state right after `setUp()`.
+    #[arg(long)]
+    replay: Option<String>,
+    /// Draw integer arguments from a dictionary of boundary values such as
+    /// `0` and `type(uint256).max` 30% of the time.
+    #[arg(long)]
+    dictionary: bool,
+    /// Record the program counters executed by each call and mutate calls
+    /// that reach new ones, instead of only generating random calls.
+    #[arg(long)]
+    coverage: bool,
+    /// Directory to load the corpus of calls to mutate from and to save new
+    /// entries to, so that fuzzing can resume across runs.
+    #[arg(long)]
+    corpus_dir: Option<PathBuf>,
+    /// Probability of mutating a corpus entry instead of generating a fresh
+    /// call, if the corpus isn't empty.
+    #[arg(long, default_value_t = 0.5, value_parser = parse_mutation_ratio)]
+    mutation_ratio: f64,
+    /// Number of worker threads fuzzing in parallel, each with its own copy
+    /// of the deployment and an RNG seeded from the seed and its index.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    jobs: u64,
+    /// Only call target functions whose name matches this glob pattern. Can
+    /// be given multiple times to match any of the patterns.
+    #[arg(long)]
+    include: Vec<Pattern>,
+    /// Don't call target functions whose name matches this glob pattern. Can
+    /// be given multiple times.
+    #[arg(long)]
+    exclude: Vec<Pattern>,
+    /// Call the target function `NAME` with relative probability `WEIGHT`
+    /// instead of 1, e.g. `withdraw=5`. `NAME` can also be the signature of
+    /// an overload, e.g. `foo(uint256)=5`. Can be given multiple times.
+    #[arg(long = "weight", value_name = "NAME=WEIGHT", value_parser = parse_weight)]
+    weights: Vec<(String, u32)>,
+    /// Print the min, max and mean gas used by the successful calls to each
+    /// target function in the summary of the run.
+    #[arg(long)]
+    gas_report: bool,
+    /// Seconds between progress reports shown with `-v`, with the iterations
+    /// per second, coverage, corpus size and calls to each target function.
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    stats_interval: u64,
+    /// Write a Foundry test reproducing the crash found, if any, to this
+    /// path, e.g. `test/Crash.t.sol`. It deploys the invariant checker,
+    /// replays the shrunk call sequence and checks that the crash happens.
+    #[arg(long, value_name = "PATH")]
+    emit_foundry: Option<PathBuf>,
+    /// Show progress with `-v`, and every call and emitted event with `-vv`.
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
 }
 
-pub fn deploy_target(runner: &mut Evm, invariant_checker_address: Address) -> Address {
-    let deploy_target_calldata = function_selector("setUp()");
-    runner.call(invariant_checker_address, deploy_target_calldata.to_vec());
-    let target_calldata = function_selector("inv()");
-    let (_, target) = runner.call(invariant_checker_address, target_calldata.to_vec());
-    Address::from_slice(&target[12..32])
+/// Check that an import remapping has the form `prefix=path`.
+fn parse_remapping(str: &str) -> Result<String, String> {
+    if !str.contains('=') {
+        return Err("expected PREFIX=PATH".to_owned());
+    }
+    Ok(str.to_owned())
+}
+
+/// Parse a function weight of the form `name=weight`.
+fn parse_weight(str: &str) -> Result<(String, u32), String> {
+    let (name, weight) = str
+        .split_once('=')
+        .ok_or_else(|| "expected NAME=WEIGHT".to_owned())?;
+    let weight = weight
+        .parse()
+        .map_err(|err| format!("invalid weight: {err}"))?;
+    Ok((name.to_owned(), weight))
 }
 
-pub fn check_invariant(runner: &mut Evm, invariant_checker_address: Address) -> bool {
-    let invariant_check_function_signature = "invariant_neverFalse()";
-    let (_, result) = runner.call(
-        invariant_checker_address,
-        function_selector(invariant_check_function_signature).to_vec(),
-    );
-    assert_eq!(result.len(), 32);
-    // Interpret the last byte of `result` as boolean
-    assert_eq!(result[..31], vec![0; 31]);
-    result[31] == 1
+/// Parse a mutation ratio between 0 and 1.
+fn parse_mutation_ratio(str: &str) -> Result<f64, String> {
+    let ratio = str.parse::<f64>().map_err(|err| err.to_string())?;
+    if !(0.0..=1.0).contains(&ratio) {
+        return Err("must be between 0 and 1".to_owned());
+    }
+    Ok(ratio)
+}
+
+/// Parse a hex-encoded address, with or without `0x` prefix.
+fn parse_address(str: &str) -> Result<Address, String> {
+    let bytes =
+        hex::decode(str.strip_prefix("0x").unwrap_or(str)).map_err(|err| err.to_string())?;
+    if bytes.len() != 20 {
+        return Err(format!("expected 20 bytes, got {}", bytes.len()));
+    }
+    Ok(Address::from_slice(&bytes))
 }
 
 /// Main fuzzer loop.
 fn main() {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    if let Some(config) = &args.config {
+        ConfigFile::load(config).apply(&mut args, &matches);
+    }
+    // Only warnings and crashes are shown by default, `RUST_LOG` overrides
+    // the level chosen with `-v`.
+    let level = match args.verbose {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .init();
+    if args.senders.is_empty() {
+        args.senders.push(DEFAULT_CALLER);
+    }
+    let crash_file = args.replay.as_deref().map(CrashFile::load);
+    if let Some(crash_file) = &crash_file {
+        if args.evm_version.is_none() {
+            args.evm_version = crash_file.evm_version.clone();
+        }
+    }
     // Compile the Solidity source.
-    let target_name = "InvariantBreaker";
-    let invariant_checker_name = "InvariantTest";
-    let output = compile_solidity(target_name, invariant_checker_name);
-    let mut runner = Evm::default();
-    let invariant_checker_address =
-        deploy_invariant_checker(&mut runner, output.invariant_checker.0);
-    let target_address = deploy_target(&mut runner, invariant_checker_address);
-
-    let solidity_fuzzer = SolidityFuzzer::new(output.target_abi);
-    let mut iterations: u64 = 0;
-    loop {
-        iterations += 1;
-        let calldata = solidity_fuzzer.generate_random_calldata();
-        // Run the contract call inside catch_unwind to capture panics.
-        let result = panic::catch_unwind(AssertUnwindSafe(|| {
-            runner.call(target_address, calldata.clone());
-        }))
-        .and_then(
-            |_| match check_invariant(&mut runner, invariant_checker_address) {
-                true => Ok(()),
-                false => Err(Box::new(())),
-            },
+    let solc = args
+        .solc
+        .clone()
+        .or_else(|| env::var("SOLC").ok())
+        .unwrap_or_else(|| "solc".to_owned());
+    let output = compile_solidity(
+        &solc,
+        &args.sources,
+        &args.remappings,
+        &args.targets,
+        &args.invariant,
+        args.evm_version.as_deref(),
+        args.optimizer_runs,
+    )
+    .unwrap_or_else(|err| panic!("{err}"));
+    let seed = match &crash_file {
+        Some(crash_file) => crash_file.seed,
+        None => args.seed.unwrap_or_else(rand::random),
+    };
+    info!("Fuzzing with seed {seed}");
+    let config = FuzzConfig {
+        seed,
+        senders: args.senders,
+        isolated: args.isolated,
+        depth: args.depth,
+        spec_id: args
+            .evm_version
+            .as_deref()
+            .map(|evm_version| spec_id(evm_version).unwrap()),
+        max_time_delay: args.max_time_delay,
+        panics: args.panics,
+        duration: args.duration.map(Duration::from_secs),
+        max_iterations: args.max_iterations,
+        dictionary: args.dictionary,
+        coverage: args.coverage,
+        corpus_dir: args.corpus_dir,
+        mutation_ratio: args.mutation_ratio,
+        jobs: args.jobs,
+        include: args.include,
+        exclude: args.exclude,
+        weights: args.weights,
+        setup_function: args.setup_function,
+        targets_function: args.targets_function,
+        gas_report: args.gas_report,
+        stats_interval: Duration::from_secs(args.stats_interval),
+    };
+    let mut fuzzer = Fuzzer::new(output, config).unwrap_or_else(|err| panic!("{err}"));
+
+    if let Some(crash_file) = crash_file {
+        let calls = crash_file.calls();
+        info!(
+            "Replaying call sequence:\n{}",
+            format_calls(fuzzer.target_fuzzer(), &calls)
+        );
+        match fuzzer.replay(&calls) {
+            Some(crash) => {
+                error!("Crash reproduced! {crash}");
+                process::exit(1);
+            }
+            None => {
+                warn!("Crash not reproduced, expected: {}", crash_file.crash);
+                return;
+            }
+        }
+    }
+
+    let result = fuzzer.run();
+    let target_fuzzer = fuzzer.target_fuzzer();
+    if let Some(found_crash) = &result.crash {
+        error!(
+            "Crash found after {} iterations with seed {}!",
+            found_crash.iterations, seed
         );
-        // If a panic is detected, report and exit.
-        if result.is_err() {
-            println!("Crash found after {} iterations!", iterations);
-            println!("Crashing input: {:?}", calldata);
-            break;
+        if args.jobs > 1 {
+            error!("Found by worker {}", found_crash.worker);
         }
-        // Print progress every 100,000 iterations.
-        if iterations % 100_000 == 0 {
-            println!("Tested {} iterations without a crash...", iterations);
+        error!("{}", found_crash.crash);
+        error!(
+            "Crashing call sequence:\n{}",
+            format_calls(target_fuzzer, &found_crash.calls)
+        );
+        error!(
+            "Shrunk call sequence:\n{}",
+            format_calls(target_fuzzer, &found_crash.shrunk_calls)
+        );
+        let path = CrashFile::new(
+            target_fuzzer,
+            seed,
+            args.evm_version.as_deref(),
+            &found_crash.crash,
+            &found_crash.shrunk_calls,
+        )
+        .save();
+        error!("Saved crash to {}", path.display());
+        if let Some(path) = &args.emit_foundry {
+            match foundry_test(
+                &fuzzer,
+                &args.sources,
+                &args.invariant,
+                &found_crash.crash,
+                &found_crash.shrunk_calls,
+            ) {
+                Some(test) => {
+                    fs::write(path, test)
+                        .unwrap_or_else(|err| panic!("Failed to write {}: {err}", path.display()));
+                    error!("Saved Foundry test to {}", path.display());
+                }
+                None => warn!(
+                    "Crash doesn't reproduce right after setUp(), not writing {}",
+                    path.display()
+                ),
+            }
+        }
+    }
+    if args.duration.is_some() || args.max_iterations.is_some() || args.gas_report {
+        print_summary(&result);
+    }
+    // Let scripts and CI pipelines detect the crash.
+    if result.crash.is_some() {
+        process::exit(1);
+    }
+}
+
+/// Print the number of iterations and calls to each function of a run, keyed
+/// by signature, and the gas report, if any. The summary is the output of the
+/// run rather than a diagnostic, so it goes to stdout instead of the log.
+fn print_summary(result: &FuzzResult) {
+    println!("Summary:");
+    println!("  iterations: {}", result.iterations);
+    println!("  calls per function:");
+    for (name, calls) in &result.calls_per_function {
+        println!("    {name}: {calls}");
+    }
+    if let Some(gas_report) = &result.gas_report {
+        println!("  gas per function:");
+        for (name, stats) in gas_report {
+            println!(
+                "    {name}: min {}, max {}, mean {}",
+                stats.min,
+                stats.max,
+                stats.mean()
+            );
         }
     }
+    println!("  crash found: {}", result.crash.is_some());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse the command line arguments `cli`, then apply `config` to them
+    /// like `main` does.
+    fn args(cli: &[&str], config: ConfigFile) -> Args {
+        let matches = Args::command()
+            .try_get_matches_from(std::iter::once("simple_fuzzer").chain(cli.iter().copied()))
+            .unwrap();
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        config.apply(&mut args, &matches);
+        args
+    }
+
+    #[test]
+    fn config_file_overrides_defaults() {
+        let path = env::temp_dir().join(format!("simple_fuzzer_{}.toml", process::id()));
+        fs::write(
+            &path,
+            r#"
+                sources = ["src/Vault.sol"]
+                invariant = "VaultTest"
+                seed = 5
+                max-time-delay = 60
+                senders = ["0x0000000000000000000000000000000000000001"]
+                include = ["deposit*"]
+                weights = { withdraw = 3 }
+            "#,
+        )
+        .unwrap();
+        let config = ConfigFile::load(&path);
+        fs::remove_file(&path).unwrap();
+        let args = args(&[], config);
+        assert_eq!(args.sources, ["src/Vault.sol"]);
+        assert_eq!(args.invariant, "VaultTest");
+        assert_eq!(args.seed, Some(5));
+        assert_eq!(args.max_time_delay, 60);
+        assert_eq!(args.senders, [Address::with_last_byte(1)]);
+        assert_eq!(args.include, [Pattern::new("deposit*").unwrap()]);
+        assert_eq!(args.weights, [("withdraw".to_owned(), 3)]);
+        // Settings missing from the file keep their defaults.
+        assert_eq!(args.targets, ["InvariantBreaker"]);
+        assert_eq!(args.jobs, 1);
+    }
+
+    #[test]
+    fn command_line_overrides_config_file() {
+        let config = toml::from_str(
+            r#"
+                invariant = "VaultTest"
+                seed = 5
+                max-time-delay = 60
+                include = ["deposit*"]
+            "#,
+        )
+        .unwrap();
+        let args = args(
+            &[
+                "--seed",
+                "1",
+                "--max-time-delay",
+                "0",
+                "--include",
+                "withdraw*",
+            ],
+            config,
+        );
+        assert_eq!(args.seed, Some(1));
+        // Given explicitly, even though it is the default.
+        assert_eq!(args.max_time_delay, 0);
+        assert_eq!(args.include, [Pattern::new("withdraw*").unwrap()]);
+        assert_eq!(args.invariant, "VaultTest");
+    }
+
+    #[test]
+    fn crash_files_record_the_evm_version() {
+        let crash_file = CrashFile {
+            seed: 1,
+            evm_version: Some("paris".to_owned()),
+            crash: "Broken invariant: invariant_x()".to_owned(),
+            calls: vec![],
+        };
+        let json = serde_json::to_string(&crash_file).unwrap();
+        let loaded: CrashFile = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.evm_version.as_deref(), Some("paris"));
+        // Crash files saved without an EVM version still load.
+        let loaded: CrashFile =
+            serde_json::from_str(r#"{ "seed": 1, "crash": "", "calls": [] }"#).unwrap();
+        assert_eq!(loaded.evm_version, None);
+    }
+
+    #[test]
+    fn unknown_config_keys_are_rejected() {
+        assert!(toml::from_str::<ConfigFile>("max-iteration = 10").is_err());
+    }
+
+    #[test]
+    fn out_of_range_mutation_ratios_are_rejected() {
+        let parse = |ratio: &str| {
+            Args::command().try_get_matches_from(["simple_fuzzer", "--mutation-ratio", ratio])
+        };
+        assert!(parse("0.25").is_ok());
+        assert!(parse("1.5").is_err());
+        assert!(parse("-0.1").is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid mutation-ratio in config")]
+    fn out_of_range_config_mutation_ratios_are_rejected() {
+        args(&[], toml::from_str("mutation-ratio = 1.5").unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid senders in config")]
+    fn invalid_config_values_are_rejected() {
+        args(&[], toml::from_str(r#"senders = ["0x1234"]"#).unwrap());
+    }
 }
@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::process::{Command, Stdio};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+/// The JSON structure output by solc with --combined-json bin,abi,ast.
+#[derive(Deserialize)]
+struct ParsedResult {
+    contracts: HashMap<String, ParsedContract>,
+    #[serde(default)]
+    sources: HashMap<String, ParsedSource>,
+}
+
+#[derive(Deserialize)]
+struct ParsedSource {
+    #[serde(rename = "AST", default)]
+    ast: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct ParsedContract {
+    abi: Vec<ParsedFunction>,
+    bin: String,
+}
+
+/// An entry of a contract ABI, e.g. a function or the constructor.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ParsedFunction {
+    /// Kind of the entry, e.g. `function`, `constructor` or `event`.
+    #[serde(rename = "type")]
+    pub(crate) ty: String,
+    /// Name of the entry, empty for the constructor.
+    #[serde(default)]
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) inputs: Vec<ParsedParam>,
+    /// One of `pure`, `view`, `nonpayable` or `payable` for functions.
+    #[serde(rename = "stateMutability", default)]
+    pub(crate) state_mutability: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ParsedParam {
+    #[serde(rename = "type")]
+    pub(crate) ty: String,
+    #[serde(rename = "internalType")]
+    pub(crate) internal_type: String,
+    /// Members of a tuple type, empty for all other types.
+    #[serde(default)]
+    pub(crate) components: Vec<ParsedParam>,
+    /// Number of variants of an enum type, or of the elements of an array
+    /// type, if known. Not part of the ABI, see [`compile_solidity`].
+    #[serde(skip)]
+    pub(crate) enum_variants: Option<usize>,
+}
+
+/// The contracts compiled by [`compile_solidity`].
+pub struct CompilationOutput {
+    pub(crate) invariant_checker: (Vec<u8>, Vec<ParsedFunction>),
+    /// Name of each target contract as looked up, along with its ABI.
+    pub(crate) targets: Vec<(String, Vec<ParsedFunction>)>,
+}
+
+/// Compiles Solidity source code (via solc) and returns the creation
+/// bytecode and ABI of the invariant checker, and the ABIs of the target
+/// contracts `target_names`. It reads the Solidity sources from
+/// `source_paths`, resolving imports with `remappings` of the form
+/// `prefix=path`. If `evm_version` is given, bytecode is generated for that
+/// EVM version. The compiler is run from `solc`, a path or a command on
+/// `PATH`, with the optimizer enabled if `optimizer_runs` is given.
+///
+/// Contracts are looked up by `file:Name`, or by `Name` alone if no other
+/// compiled contract has that name. Enum parameters in the ABIs are annotated
+/// with their number of variants, looked up in the ASTs of the sources.
+pub fn compile_solidity(
+    solc: &str,
+    source_paths: &[String],
+    remappings: &[String],
+    target_names: &[String],
+    invariant_checker_name: &str,
+    evm_version: Option<&str>,
+    optimizer_runs: Option<u32>,
+) -> Result<CompilationOutput, CompileError> {
+    let mut command = Command::new(solc);
+    command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .arg("--combined-json")
+        .arg("bin,abi,ast")
+        .args(remappings)
+        .args(source_paths);
+    if let Some(evm_version) = evm_version {
+        command.arg("--evm-version").arg(evm_version);
+    }
+    if let Some(optimizer_runs) = optimizer_runs {
+        command
+            .arg("--optimize")
+            .arg("--optimize-runs")
+            .arg(optimizer_runs.to_string());
+    }
+    let process = command.spawn().map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => CompileError::SolcNotFound(solc.to_owned()),
+        _ => CompileError::Spawn(solc.to_owned(), err),
+    })?;
+    let output = process
+        .wait_with_output()
+        .map_err(|err| CompileError::Spawn(solc.to_owned(), err))?;
+    if !output.status.success() {
+        return Err(CompileError::Solc(
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ));
+    }
+    parse_solc_output(
+        &String::from_utf8_lossy(&output.stdout),
+        target_names,
+        invariant_checker_name,
+    )
+}
+
+/// Looks up contracts like [`compile_solidity`] in `output`, the output of
+/// an earlier `solc --combined-json bin,abi,ast` run.
+pub fn parse_solc_output(
+    output: &str,
+    target_names: &[String],
+    invariant_checker_name: &str,
+) -> Result<CompilationOutput, CompileError> {
+    let parsed_result: ParsedResult = serde_json::from_str(output)
+        .map_err(|err| CompileError::InvalidOutput(err, output.to_owned()))?;
+    let mut enums = HashMap::new();
+    for source in parsed_result.sources.values() {
+        collect_enums(&source.ast, &mut enums);
+    }
+    let with_enum_variants = |abi: &[ParsedFunction]| {
+        let mut abi = abi.to_vec();
+        for function in &mut abi {
+            set_enum_variants(&mut function.inputs, &enums);
+        }
+        abi
+    };
+    let targets = target_names
+        .iter()
+        .map(|name| {
+            let target = find_contract(&parsed_result.contracts, name)?;
+            Ok((name.clone(), with_enum_variants(&target.abi)))
+        })
+        .collect::<Result<_, _>>()?;
+    let invariant_checker = find_contract(&parsed_result.contracts, invariant_checker_name)?;
+    Ok(CompilationOutput {
+        targets,
+        invariant_checker: (
+            hex::decode(invariant_checker.bin.as_str()).map_err(|err| {
+                CompileError::InvalidBytecode(invariant_checker_name.to_owned(), err)
+            })?,
+            with_enum_variants(&invariant_checker.abi),
+        ),
+    })
+}
+
+/// Add the number of members of each enum defined in the AST `node` to
+/// `enums`, keyed by canonical name, e.g. `Vault.State`. Enums defined with
+/// the same name but a different number of members in several sources map to
+/// `None`.
+fn collect_enums(node: &serde_json::Value, enums: &mut HashMap<String, Option<usize>>) {
+    match node {
+        serde_json::Value::Object(object) => {
+            if object.get("nodeType").and_then(|ty| ty.as_str()) == Some("EnumDefinition") {
+                let name = object.get("canonicalName").and_then(|name| name.as_str());
+                let members = object.get("members").and_then(|members| members.as_array());
+                if let (Some(name), Some(members)) = (name, members) {
+                    let variants = enums.entry(name.to_owned()).or_insert(Some(members.len()));
+                    if *variants != Some(members.len()) {
+                        *variants = None;
+                    }
+                }
+            }
+            object
+                .values()
+                .for_each(|child| collect_enums(child, enums));
+        }
+        serde_json::Value::Array(children) => {
+            children
+                .iter()
+                .for_each(|child| collect_enums(child, enums));
+        }
+        _ => {}
+    }
+}
+
+/// Set the number of variants of the `params` whose internal type is an
+/// enum, e.g. `enum Vault.State`, or an array of them, to the one in `enums`.
+fn set_enum_variants(params: &mut [ParsedParam], enums: &HashMap<String, Option<usize>>) {
+    for param in params {
+        if let Some(name) = param.internal_type.strip_prefix("enum ") {
+            // Drop the dimensions of an array type, e.g. `[2][]`.
+            let name = name.split('[').next().unwrap();
+            param.enum_variants = enums.get(name).copied().flatten();
+        }
+        set_enum_variants(&mut param.components, enums);
+    }
+}
+
+/// Error compiling the Solidity sources with [`compile_solidity`].
+#[derive(Debug)]
+pub enum CompileError {
+    /// The solc binary with the given path wasn't found.
+    SolcNotFound(String),
+    /// The solc binary with the given path couldn't be run.
+    Spawn(String, io::Error),
+    /// solc failed with the given error output, e.g. because a source
+    /// doesn't compile.
+    Solc(String),
+    /// The output of solc wasn't the expected JSON.
+    InvalidOutput(serde_json::Error, String),
+    /// No compiled contract has the given name.
+    ContractNotFound(String),
+    /// Several compiled contracts have the given name, identified by the
+    /// given `file:Name`s.
+    AmbiguousContract(String, Vec<String>),
+    /// The bytecode of the given contract isn't valid hex, e.g. because it
+    /// has unlinked library placeholders.
+    InvalidBytecode(String, hex::FromHexError),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::SolcNotFound(solc) => write!(f, "Command '{solc}' not found"),
+            CompileError::Spawn(solc, err) => {
+                write!(f, "Failed to spawn process with command '{solc}':\n{err}")
+            }
+            CompileError::Solc(stderr) => write!(f, "Compilation failed:\n{stderr}"),
+            CompileError::InvalidOutput(err, output) => {
+                write!(
+                    f,
+                    "Failed to parse solc JSON output: {err}\nOutput: {output}"
+                )
+            }
+            CompileError::ContractNotFound(name) => write!(f, "Contract '{name}' not found"),
+            CompileError::AmbiguousContract(name, ids) => write!(
+                f,
+                "Contract name '{name}' is ambiguous, use one of {}",
+                ids.join(", ")
+            ),
+            CompileError::InvalidBytecode(name, err) => {
+                write!(f, "Invalid hex in bytecode of contract '{name}': {err}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CompileError::Spawn(_, err) => Some(err),
+            CompileError::InvalidOutput(err, _) => Some(err),
+            CompileError::InvalidBytecode(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Return the contract identified by `name` in the solc output `contracts`,
+/// either `file:Name` or `Name` alone. Fails if there is no such contract, or
+/// if `Name` alone is ambiguous.
+fn find_contract<'a>(
+    contracts: &'a HashMap<String, ParsedContract>,
+    name: &str,
+) -> Result<&'a ParsedContract, CompileError> {
+    if name.contains(':') {
+        return contracts
+            .get(name)
+            .ok_or_else(|| CompileError::ContractNotFound(name.to_owned()));
+    }
+    let matches = contracts
+        .iter()
+        .filter(|(id, _)| id.rsplit_once(':').map(|(_, contract_name)| contract_name) == Some(name))
+        .collect_vec();
+    match matches[..] {
+        [(_, contract)] => Ok(contract),
+        [] => Err(CompileError::ContractNotFound(name.to_owned())),
+        _ => Err(CompileError::AmbiguousContract(
+            name.to_owned(),
+            matches
+                .iter()
+                .map(|(id, _)| id.to_string())
+                .sorted()
+                .collect(),
+        )),
+    }
+}
+
+/// Return the constructor entry of `abi`, if the contract declares one.
+pub fn constructor(abi: &[ParsedFunction]) -> Option<&ParsedFunction> {
+    abi.iter().find(|function| function.ty == "constructor")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enum_variants_are_read_from_the_ast() {
+        let enum_definition = |name: &str, members: usize| {
+            serde_json::json!({
+                "nodeType": "EnumDefinition",
+                "canonicalName": name,
+                "members": vec![serde_json::json!({ "nodeType": "EnumValue" }); members],
+            })
+        };
+        let mut enums = HashMap::new();
+        collect_enums(
+            &serde_json::json!({
+                "nodeType": "SourceUnit",
+                "nodes": [{
+                    "nodeType": "ContractDefinition",
+                    "nodes": [enum_definition("Vault.State", 3), enum_definition("Color", 2)],
+                }],
+            }),
+            &mut enums,
+        );
+        // Defined again with a different number of members in another source.
+        collect_enums(&enum_definition("Color", 4), &mut enums);
+        assert_eq!(
+            enums,
+            HashMap::from([
+                ("Vault.State".to_owned(), Some(3)),
+                ("Color".to_owned(), None)
+            ])
+        );
+
+        let mut params: Vec<ParsedParam> = serde_json::from_value(serde_json::json!([
+            { "type": "uint8", "internalType": "enum Vault.State" },
+            { "type": "uint8[2][]", "internalType": "enum Vault.State[2][]" },
+            { "type": "uint8", "internalType": "enum Color" },
+            { "type": "uint8", "internalType": "uint8" },
+            {
+                "type": "tuple",
+                "internalType": "struct Vault.Position",
+                "components": [{ "type": "uint8", "internalType": "enum Vault.State" }],
+            },
+        ]))
+        .unwrap();
+        set_enum_variants(&mut params, &enums);
+        let variants = params
+            .iter()
+            .map(|param| param.enum_variants)
+            .collect::<Vec<_>>();
+        assert_eq!(variants, [Some(3), Some(3), None, None, None]);
+        assert_eq!(params[4].components[0].enum_variants, Some(3));
+    }
+
+    #[test]
+    fn solc_failures_are_errors() {
+        let compile =
+            |solc| compile_solidity(solc, &[], &[], &["Target".to_owned()], "Test", None, None);
+        assert!(matches!(
+            compile("simple_fuzzer_missing_solc"),
+            Err(CompileError::SolcNotFound(_))
+        ));
+        // `false` exits with a nonzero status without output.
+        assert!(matches!(compile("false"), Err(CompileError::Solc(_))));
+        // `true` exits successfully without output.
+        assert!(matches!(
+            compile("true"),
+            Err(CompileError::InvalidOutput(..))
+        ));
+    }
+}
@@ -0,0 +1,16 @@
+//! Helpers shared by the unit tests, the integration tests and the
+//! benchmarks. Not part of the public API.
+
+use crate::solc::{parse_solc_output, CompilationOutput};
+
+/// Look up `targets` and `invariant_checker` in the hand-assembled contracts
+/// of `tests/fixtures`.
+pub fn compile_fixtures(targets: &[&str], invariant_checker: &str) -> CompilationOutput {
+    let targets: Vec<String> = targets.iter().map(|name| name.to_string()).collect();
+    parse_solc_output(
+        include_str!("../tests/fixtures/contracts.json"),
+        &targets,
+        invariant_checker,
+    )
+    .unwrap()
+}
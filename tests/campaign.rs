@@ -0,0 +1,87 @@
+use glob::Pattern;
+use simple_fuzzer::test_support::compile_fixtures as compile;
+use simple_fuzzer::{Crash, FuzzConfig, Fuzzer, SetupError};
+
+#[test]
+fn campaign_finds_broken_invariant() {
+    let config = FuzzConfig {
+        seed: 1,
+        jobs: 2,
+        coverage: true,
+        max_iterations: Some(100_000),
+        ..FuzzConfig::default()
+    };
+    let mut fuzzer = Fuzzer::new(compile(&["InvariantBreaker"], "InvariantTest"), config).unwrap();
+    let result = fuzzer.run();
+    let found = result.crash.expect("invariant_neverFalse() is breakable");
+    let crash = Crash::BrokenInvariant("invariant_neverFalse()".to_owned());
+    assert_eq!(found.crash, crash);
+    assert!(found.iterations <= result.iterations);
+    assert_eq!(found.shrunk_calls.len(), 2);
+    assert_eq!(fuzzer.replay(&found.calls), Some(crash.clone()));
+    assert_eq!(fuzzer.replay(&found.shrunk_calls), Some(crash));
+}
+
+#[test]
+fn setup_errors_are_returned() {
+    let err = |targets: &[&str], invariant_checker: &str, config: FuzzConfig| {
+        Fuzzer::new(compile(targets, invariant_checker), config)
+            .err()
+            .unwrap()
+    };
+    assert!(matches!(
+        err(&["Reverter"], "InvariantBreaker", FuzzConfig::default()),
+        SetupError::NoInvariants
+    ));
+    assert!(matches!(
+        err(&["Reverter", "Reverter"], "ReverterTest", FuzzConfig::default()),
+        SetupError::DuplicateTarget(name) if name == "Reverter"
+    ));
+    assert!(matches!(
+        err(
+            &["Reverter", "InvariantBreaker"],
+            "ReverterTest",
+            FuzzConfig::default()
+        ),
+        SetupError::TargetCount(function, 1) if function == "inv"
+    ));
+    let config = FuzzConfig {
+        include: vec![Pattern::new("missing").unwrap()],
+        ..FuzzConfig::default()
+    };
+    assert!(matches!(
+        err(&["Reverter"], "ReverterTest", config),
+        SetupError::NoFunctions
+    ));
+    let config = FuzzConfig {
+        weights: vec![("missing".to_owned(), 2)],
+        ..FuzzConfig::default()
+    };
+    assert!(matches!(
+        err(&["Reverter"], "ReverterTest", config),
+        SetupError::UnknownWeight(name) if name == "missing"
+    ));
+    let config = FuzzConfig {
+        mutation_ratio: 1.5,
+        ..FuzzConfig::default()
+    };
+    assert!(matches!(
+        err(&["Reverter"], "ReverterTest", config),
+        SetupError::InvalidMutationRatio(ratio) if ratio == 1.5
+    ));
+    let config = FuzzConfig {
+        jobs: 0,
+        ..FuzzConfig::default()
+    };
+    assert!(matches!(
+        err(&["Reverter"], "ReverterTest", config),
+        SetupError::NoJobs
+    ));
+    let config = FuzzConfig {
+        setup_function: "deploy".to_owned(),
+        ..FuzzConfig::default()
+    };
+    let err = err(&["Reverter"], "ReverterTest", config);
+    assert!(matches!(&err, SetupError::CallFailed(function, _) if function == "deploy"));
+    assert_eq!(err.to_string(), "deploy() reverted: empty revert");
+}
@@ -0,0 +1,59 @@
+// SPDX-License-Identifier: MIT
+
+// Contracts used by the tests along with `InvariantBreaker` and
+// `InvariantTest` from `contract/contract.sol`. `contracts.json` holds
+// hand-assembled bytecode behaving like these contracts in the format of
+// `solc --combined-json bin,abi`, so that the tests don't need solc.
+
+pragma solidity ^0.8.0;
+
+contract Reverter {
+    bool public armed;
+
+    function fail(uint256) public {
+        revert();
+    }
+
+    function arm() public {
+        armed = true;
+    }
+}
+
+contract ReverterTest {
+    Reverter public inv;
+
+    function setUp() public {
+        inv = new Reverter();
+    }
+
+    function invariant_notArmed() public view returns (bool) {
+        assert(!inv.armed());
+        return true;
+    }
+}
+
+contract OddInvariants {
+    Reverter public inv;
+
+    function setUp() public {
+        inv = new Reverter();
+    }
+
+    function invariant_true() public pure returns (bool) {
+        return true;
+    }
+
+    function invariant_false() public pure returns (bool) {
+        return false;
+    }
+
+    function invariant_empty() public pure {}
+
+    function invariant_word() public pure returns (uint256) {
+        return type(uint256).max;
+    }
+
+    function invariant_reverts() public pure returns (bool) {
+        revert();
+    }
+}